@@ -1,13 +1,14 @@
 use serde::{Deserialize, Serialize};
 
 // https://www.alphavantage.co/documentation/#earnings-calendar
+// Finnhub earnings don't have the name, fiscal date and currency
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Earning {
-    symbol: String,
-    name: String,
-    report_date: String,
-    fiscal_date_ending: String,
+    pub symbol: String,
+    pub name: Option<String>,
+    pub report_date: String,
+    pub fiscal_date_ending: Option<String>,
     pub estimate: Option<f32>,
-    currency: String,
+    pub currency: Option<String>,
 }
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+
+use crate::{candles::parse_date, error::Provider, finnhub_api::symbol_quote::SymbolQuoteExtended};
+
+// https://www.alphavantage.co/documentation/#latestprice
+#[derive(Deserialize, Debug)]
+pub struct GlobalQuoteResponse {
+    #[serde(rename = "Global Quote")]
+    pub quote: GlobalQuote,
+}
+
+/**
+ * All values are strings. Unknown symbols get an empty object, hence the defaults.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct GlobalQuote {
+    #[serde(rename = "02. open")]
+    open: String,
+    #[serde(rename = "03. high")]
+    high: String,
+    #[serde(rename = "04. low")]
+    low: String,
+    #[serde(rename = "05. price")]
+    price: String,
+    #[serde(rename = "07. latest trading day")]
+    latest_trading_day: String,
+    #[serde(rename = "08. previous close")]
+    previous_close: String,
+    #[serde(rename = "09. change")]
+    change: String,
+    #[serde(rename = "10. change percent")]
+    change_percent: String,
+}
+
+impl GlobalQuote {
+    /**
+     * The quote in the shape of a Finnhub quote, `None` if a value is missing or invalid.
+     * The timestamp is the start of the latest trading day.
+     */
+    pub fn into_quote(self, symbol: &str, name: &str) -> Option<SymbolQuoteExtended> {
+        let number = |value: &str| value.trim_end_matches('%').parse::<f32>().ok();
+
+        Some(SymbolQuoteExtended {
+            current_price: number(&self.price)?,
+            delta: number(&self.change)?,
+            delta_percent: number(&self.change_percent)?,
+            high: number(&self.high)?,
+            low: number(&self.low)?,
+            open: number(&self.open)?,
+            previous_close: number(&self.previous_close)?,
+            timestamp: parse_date(&self.latest_trading_day)? as u128,
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            provider: Provider::AlphaVantage,
+            rate_limit_info: None,
+        })
+    }
+}
@@ -1,98 +1,416 @@
 use super::{
+    earnings_calendar::Earning,
+    global_quote::GlobalQuoteResponse,
     market_status::{MarketStatusInfo, MarketStatusResponse},
     news_sentiment::{NewsSentimentFeedEntry, NewsSentimentResponse},
+    time_series::parse_time_series,
 };
+use crate::candles::{unix_now, Candle, CandleRange, Resolution};
+use crate::error::{payload_snippet, Provider};
+use crate::finnhub_api::symbol_quote::SymbolQuoteExtended;
+use crate::health::{ErrorKind, ProviderHealth, ProviderHealthSnapshot};
+use crate::logging;
+use crate::rate_limit::{DailyQuota, QuotaUsage};
+use crate::retry::{RetryPolicy, RetryStatsSnapshot, Retryable};
+use crate::single_flight::SingleFlight;
+use csv::ReaderBuilder;
 use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{sync::Arc, time::Duration};
+use url::Url;
 
-const BASE_URL: &str = "https://www.alphavantage.co/query?function=";
+const BASE_URL: &str = "https://www.alphavantage.co/query";
 
-#[derive(thiserror::Error, Debug)]
+// Free plan
+const REQUESTS_PER_DAY: u32 = 25;
+
+// 100 trading days of the compact daily time series, with weekends and holidays
+const COMPACT_DAILY_SECS: i64 = 130 * 24 * 60 * 60;
+
+#[derive(thiserror::Error, Debug, Clone)]
 pub enum AlphaVantageError {
-    #[error("Failed async fetching the market status")]
-    AsyncRequestFailed(#[from] reqwest::Error),
+    #[error("Alpha Vantage responded to the {endpoint} request with HTTP status {status}")]
+    HttpStatus { endpoint: &'static str, status: u16 },
+    /**
+     * `daily` if the daily limit is used up, otherwise the per-minute limit was hit, which
+     * clears within a minute
+     */
+    #[error("The Alpha Vantage rate limit is exhausted: {message}")]
+    RateLimited { message: String, daily: bool },
+    #[error("Failed to deserialize the Alpha Vantage {endpoint} response ({message}): {snippet}")]
+    Deserialization {
+        endpoint: &'static str,
+        message: String,
+        snippet: String,
+    },
+    #[error("Alpha Vantage has no quote for the symbol {symbol}")]
+    SymbolNotFound { symbol: String },
+    #[error("The Alpha Vantage API key was rejected")]
+    InvalidApiKey,
+    #[error("Alpha Vantage rejected the {endpoint} request: {message}")]
+    RequestRejected {
+        endpoint: &'static str,
+        message: String,
+    },
+    #[error("The Alpha Vantage {endpoint} request timed out")]
+    Timeout { endpoint: &'static str },
+    #[error("The Alpha Vantage {endpoint} request failed: {message}")]
+    RequestFailed {
+        endpoint: &'static str,
+        message: String,
+    },
+    #[error("Failed parsing the earnings calendar CSV: {0}")]
+    CsvParsingFailed(String),
+}
+
+impl AlphaVantageError {
+    fn from_reqwest(endpoint: &'static str, err: reqwest::Error) -> AlphaVantageError {
+        if err.is_timeout() {
+            AlphaVantageError::Timeout { endpoint }
+        } else {
+            AlphaVantageError::RequestFailed {
+                endpoint,
+                // The URL contains the API key
+                message: err.without_url().to_string(),
+            }
+        }
+    }
+
+    /**
+     * Alpha Vantage answers with HTTP 200 even if the request failed. The body is then a JSON
+     * object with a single `Note`, `Information` or `Error Message` key:
+     *
+     * 1) rate limit exhausted: `{ "Note": "... API call frequency is 5 calls per minute ..." }`
+     *    or `{ "Information": "... standard API rate limit is 25 requests per day ..." }`
+     * 2) invalid or missing key: `{ "Error Message": "... the parameter apikey is invalid ..." }`
+     * 3) invalid parameters: `{ "Error Message": "Invalid API call. ..." }` or
+     *    `{ "Information": "Invalid inputs. ..." }`
+     */
+    fn from_message_body(endpoint: &'static str, body: &str) -> Option<AlphaVantageError> {
+        let value: Value = serde_json::from_str(body).ok()?;
+        let object = value.as_object()?;
+        let message = ["Note", "Information", "Error Message"]
+            .iter()
+            .find_map(|key| object.get(*key).and_then(Value::as_str))?
+            .to_string();
+        let lowercase = message.to_lowercase();
+
+        let error = if ["rate limit", "call frequency", "requests per day"]
+            .iter()
+            .any(|hint| lowercase.contains(hint))
+        {
+            // The call frequency note mentions the daily limit as well
+            let per_minute =
+                lowercase.contains("call frequency") || lowercase.contains("per minute");
+            AlphaVantageError::RateLimited {
+                daily: !per_minute && lowercase.contains("per day"),
+                message,
+            }
+        } else if lowercase.contains("apikey") || lowercase.contains("api key") {
+            AlphaVantageError::InvalidApiKey
+        } else if object.contains_key("Note") {
+            // Notes are only ever sent for exceeded call frequencies.
+            AlphaVantageError::RateLimited {
+                message,
+                daily: false,
+            }
+        } else {
+            AlphaVantageError::RequestRejected { endpoint, message }
+        };
+
+        Some(error)
+    }
+}
+
+impl ErrorKind for AlphaVantageError {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::HttpStatus { .. } => "http_status",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::Deserialization { .. } => "deserialization",
+            Self::SymbolNotFound { .. } => "symbol_not_found",
+            Self::InvalidApiKey => "invalid_api_key",
+            Self::RequestRejected { .. } => "request_rejected",
+            Self::Timeout { .. } => "timeout",
+            Self::RequestFailed { .. } => "request_failed",
+            Self::CsvParsingFailed(_) => "csv_parsing_failed",
+        }
+    }
+}
+
+impl Retryable for AlphaVantageError {
+    fn status(&self) -> Option<u16> {
+        match self {
+            Self::HttpStatus { status, .. } => Some(*status),
+            // Not even the per-minute limit is retried, it takes longer to clear than the
+            // retry policy waits and every attempt would count against the daily quota
+            _ => None,
+        }
+    }
+
+    fn is_transient(&self) -> bool {
+        matches!(self, Self::Timeout { .. } | Self::RequestFailed { .. })
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        None
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Endpoint {
     MarketStatus,
     NewsSentiment,
+    GlobalQuote,
     EarningsCalendar,
+    TimeSeriesIntraday,
+    TimeSeriesDaily,
+    TimeSeriesWeekly,
+    TimeSeriesMonthly,
 }
 
-impl ToString for Endpoint {
-    fn to_string(&self) -> String {
+impl Endpoint {
+    /**
+     * Value of the `function` query parameter.
+     */
+    pub fn function(&self) -> &'static str {
         match self {
-            Self::MarketStatus => "MARKET_STATUS".to_string(),
-            Self::NewsSentiment => "NEWS_SENTIMENT".to_string(),
-            Self::EarningsCalendar => "EARNINGS_CALENDAR".to_string(),
+            Self::MarketStatus => "MARKET_STATUS",
+            Self::NewsSentiment => "NEWS_SENTIMENT",
+            Self::GlobalQuote => "GLOBAL_QUOTE",
+            Self::EarningsCalendar => "EARNINGS_CALENDAR",
+            Self::TimeSeriesIntraday => "TIME_SERIES_INTRADAY",
+            Self::TimeSeriesDaily => "TIME_SERIES_DAILY",
+            Self::TimeSeriesWeekly => "TIME_SERIES_WEEKLY",
+            Self::TimeSeriesMonthly => "TIME_SERIES_MONTHLY",
+        }
+    }
+
+    /**
+     * Short name of the endpoint used in error messages.
+     */
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MarketStatus => "market status",
+            Self::NewsSentiment => "news sentiment",
+            Self::GlobalQuote => "global quote",
+            Self::EarningsCalendar => "earnings calendar",
+            Self::TimeSeriesIntraday => "intraday time series",
+            Self::TimeSeriesDaily => "daily time series",
+            Self::TimeSeriesWeekly => "weekly time series",
+            Self::TimeSeriesMonthly => "monthly time series",
+        }
+    }
+
+    /**
+     * Time series endpoint serving candles of the given resolution.
+     */
+    pub fn time_series(resolution: Resolution) -> Endpoint {
+        match resolution {
+            Resolution::Day => Self::TimeSeriesDaily,
+            Resolution::Week => Self::TimeSeriesWeekly,
+            Resolution::Month => Self::TimeSeriesMonthly,
+            _ => Self::TimeSeriesIntraday,
         }
     }
 }
 
-#[derive(Debug)]
+/**
+ * Cheap to clone. Clones share the HTTP connection pool, the in-flight requests and the daily
+ * quota, so concurrent identical requests from all clones only use up one call of the quota.
+ */
+#[derive(Debug, Clone)]
 pub struct AlphaVantageAPI {
+    base_url: Url,
     api_key: String,
     endpoint: Endpoint,
+    client: reqwest::Client,
+    in_flight: SingleFlight<Result<String, AlphaVantageError>>,
+    quota: Arc<DailyQuota>,
+    retry_policy: RetryPolicy,
+    health: Arc<ProviderHealth>,
 }
 
 impl AlphaVantageAPI {
-    pub fn new(api_key: &str) -> AlphaVantageAPI {
+    pub fn new(api_key: &str, client: reqwest::Client) -> AlphaVantageAPI {
         AlphaVantageAPI {
+            base_url: Url::parse(BASE_URL).expect("the Alpha Vantage API URL to be valid"),
             api_key: api_key.to_string(),
             endpoint: Endpoint::MarketStatus,
+            client,
+            in_flight: SingleFlight::new(),
+            quota: Arc::new(DailyQuota::new(REQUESTS_PER_DAY)),
+            retry_policy: RetryPolicy::default(),
+            health: Arc::new(ProviderHealth::default()),
         }
     }
 
+    /**
+     * Overrides the Alpha Vantage query URL, e.g. to point the client to a mock server.
+     */
+    pub fn base_url(&mut self, base_url: Url) -> &mut AlphaVantageAPI {
+        self.base_url = base_url;
+        self
+    }
+
     pub fn endpoint(&mut self, endpoint: Endpoint) -> &mut AlphaVantageAPI {
         self.endpoint = endpoint;
         self
     }
 
-    fn get_api_key(&self) -> String {
-        format!("&apikey={}", self.api_key)
+    pub fn quota(&mut self, quota: DailyQuota) -> &mut AlphaVantageAPI {
+        self.quota = Arc::new(quota);
+        self
+    }
+
+    pub fn retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut AlphaVantageAPI {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn retry_stats(&self) -> RetryStatsSnapshot {
+        self.retry_policy.stats()
+    }
+
+    pub fn health(&self) -> ProviderHealthSnapshot {
+        self.health.snapshot()
+    }
+
+    pub fn quota_usage(&self) -> QuotaUsage {
+        self.quota.usage()
     }
 
     /**
-     *  Possibilities for url_add
-     * 1) Endpoint::NewsSentiment: complete query string ?time_from=yyyymmddThhmmss
+     * Checks that Alpha Vantage is reachable. Any response counts, the request has no key
+     * and doesn't use up the quota.
      */
-    fn prepare_url(&self, url_add: Option<&str>) -> String {
-        if let Some(url) = url_add {
-            format!(
-                "{}{}{}{}",
-                BASE_URL,
-                self.endpoint.to_string(),
-                url,
-                self.get_api_key(),
-            )
-        } else {
-            format!(
-                "{}{}{}",
-                BASE_URL,
-                self.endpoint.to_string(),
-                self.get_api_key(),
-            )
+    pub async fn ping(&self) -> Result<(), AlphaVantageError> {
+        self.client
+            .request(Method::GET, self.base_url.clone())
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| AlphaVantageError::from_reqwest("ping", err))
+    }
+
+    /**
+     * URL of the endpoint with the encoded query parameters. Alpha Vantage only accepts the
+     * API key as a query parameter, so errors must not contain the URL.
+     */
+    fn prepare_url(&self, query: &[(&str, &str)]) -> Url {
+        let mut url = self.base_url.clone();
+        url.query_pairs_mut()
+            .append_pair("function", self.endpoint.function())
+            .extend_pairs(query)
+            .append_pair("apikey", &self.api_key);
+        url
+    }
+
+    /**
+     * Sends a GET request to the given url and returns the response body. Bodies carrying an
+     * Alpha Vantage error message are turned into the matching `AlphaVantageError`.
+     * Failed requests are retried according to the retry policy. Concurrent requests for the
+     * same url share a single upstream request.
+     */
+    async fn get_text(&self, url: Url) -> Result<String, AlphaVantageError> {
+        let result = self
+            .in_flight
+            .run(url.as_str(), || {
+                self.retry_policy.run(|| {
+                    logging::upstream_call(
+                        Provider::AlphaVantage,
+                        self.endpoint.name(),
+                        url.as_str(),
+                        self.send(url.clone()),
+                    )
+                })
+            })
+            .await;
+        self.health.record(&result);
+
+        result
+    }
+
+    async fn send(&self, url: Url) -> Result<String, AlphaVantageError> {
+        let endpoint = self.endpoint.name();
+
+        if let Err(reset_in) = self.quota.try_acquire() {
+            return Err(AlphaVantageError::RateLimited {
+                message: format!(
+                    "The daily quota is used up, it resets in {} minutes",
+                    reset_in.as_secs() / 60 + 1
+                ),
+                daily: true,
+            });
+        }
+
+        let response = self
+            .client
+            .request(Method::GET, url)
+            .send()
+            .await
+            .map_err(|err| AlphaVantageError::from_reqwest(endpoint, err))?;
+        logging::record_status(response.status());
+
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(AlphaVantageError::RateLimited {
+                message: "Too many requests".to_string(),
+                daily: false,
+            });
+        }
+        if !status.is_success() {
+            return Err(AlphaVantageError::HttpStatus {
+                endpoint,
+                status: status.as_u16(),
+            });
+        }
+
+        let body = response
+            .text()
+            .await
+            .map_err(|err| AlphaVantageError::from_reqwest(endpoint, err))?;
+
+        // Also applies to the CSV endpoints which fall back to JSON for errors.
+        match AlphaVantageError::from_message_body(endpoint, &body) {
+            Some(err) => {
+                if let AlphaVantageError::RateLimited { daily: true, .. } = err {
+                    self.quota.exhaust();
+                }
+                Err(err)
+            }
+            None => Ok(body),
         }
     }
 
+    /**
+     * Sends a GET request to the given url and deserializes the JSON response into `T`.
+     */
+    async fn get_json<T: DeserializeOwned>(&self, url: Url) -> Result<T, AlphaVantageError> {
+        let body = self.get_text(url).await?;
+
+        serde_json::from_str(&body).map_err(|err| {
+            self.response_error(AlphaVantageError::Deserialization {
+                endpoint: self.endpoint.name(),
+                message: err.to_string(),
+                snippet: payload_snippet(&body),
+            })
+        })
+    }
+
+    /**
+     * Records an error about a response body, e.g. invalid JSON, which the request itself
+     * didn't fail with.
+     */
+    fn response_error(&self, err: AlphaVantageError) -> AlphaVantageError {
+        self.health.record_error(&err);
+        err
+    }
+
     pub async fn fetch_market_status(&self) -> Result<Vec<MarketStatusInfo>, AlphaVantageError> {
-        let url = self.prepare_url(None);
-        let client = reqwest::Client::new();
-        let req = client
-            .request(Method::GET, url)
-            .build()
-            .map_err(AlphaVantageError::AsyncRequestFailed)?;
-
-        let mut res: MarketStatusResponse =
-            client
-                .execute(req)
-                .await?
-                .json()
-                .await
-                .unwrap_or(MarketStatusResponse {
-                    endpoint: "API limit reached".to_string(),
-                    markets: vec![],
-                });
+        let url = self.prepare_url(&[]);
+        let mut res: MarketStatusResponse = self.get_json(url).await?;
 
         res.markets
             .retain(|market| market.region == "United States" || market.region == "Germany");
@@ -104,21 +422,8 @@ impl AlphaVantageAPI {
         &self,
         time_from: String,
     ) -> Result<Vec<NewsSentimentFeedEntry>, AlphaVantageError> {
-        let query = format!("&time_from={time_from}T0000");
-        let url = self.prepare_url(Some(query.as_str()));
-
-        let client = reqwest::Client::new();
-        let req = client
-            .request(Method::GET, url)
-            .build()
-            .map_err(AlphaVantageError::AsyncRequestFailed)?;
-
-        let res: NewsSentimentResponse = client
-            .execute(req)
-            .await?
-            .json()
-            .await
-            .unwrap_or(NewsSentimentResponse { feed: vec![] });
+        let url = self.prepare_url(&[("time_from", &format!("{time_from}T0000"))]);
+        let res: NewsSentimentResponse = self.get_json(url).await?;
 
         Ok(res.feed)
     }
@@ -128,36 +433,167 @@ impl AlphaVantageAPI {
         ticker: String,
         time_from: String,
     ) -> Result<Vec<NewsSentimentFeedEntry>, AlphaVantageError> {
-        let query = format!("&tickers={ticker}&sort=RELEVANCE&time_from={time_from}T0000");
-        let url = self.prepare_url(Some(query.as_str()));
+        let url = self.prepare_url(&[
+            ("tickers", &ticker),
+            ("sort", "RELEVANCE"),
+            ("time_from", &format!("{time_from}T0000")),
+        ]);
+        let res: NewsSentimentResponse = self.get_json(url).await?;
 
-        let client = reqwest::Client::new();
-        let req = client
-            .request(Method::GET, url)
-            .build()
-            .map_err(AlphaVantageError::AsyncRequestFailed)?;
+        Ok(res.feed)
+    }
 
-        let res: NewsSentimentResponse = client
-            .execute(req)
-            .await?
-            .json()
-            .await
-            .unwrap_or(NewsSentimentResponse { feed: vec![] });
+    /**
+     * The latest news of all tickers.
+     */
+    pub async fn fetch_latest_news(
+        &self,
+    ) -> Result<Vec<NewsSentimentFeedEntry>, AlphaVantageError> {
+        let url = self.prepare_url(&[("sort", "LATEST")]);
+        let res: NewsSentimentResponse = self.get_json(url).await?;
 
         Ok(res.feed)
     }
 
-    pub async fn fetch_earnings_calendar(&self) -> Result<String, AlphaVantageError> {
-        let url = self.prepare_url(Some("&horizon=3month"));
-        let client = reqwest::Client::new();
-        let req = client
-            .request(Method::GET, url)
-            .build()
-            .map_err(AlphaVantageError::AsyncRequestFailed)?;
+    /**
+     * News of a ticker between two yyyy-mm-dd dates (inclusive).
+     */
+    pub async fn fetch_company_news(
+        &self,
+        ticker: &str,
+        time_from: &str,
+        time_to: &str,
+    ) -> Result<Vec<NewsSentimentFeedEntry>, AlphaVantageError> {
+        let url = self.prepare_url(&[
+            ("tickers", ticker),
+            ("sort", "LATEST"),
+            ("time_from", &format!("{}T0000", time_from.replace('-', ""))),
+            ("time_to", &format!("{}T2359", time_to.replace('-', ""))),
+        ]);
+        let res: NewsSentimentResponse = self.get_json(url).await?;
+
+        Ok(res.feed)
+    }
+
+    /**
+     * Fetches the latest price of a single symbol. `name` is only passed through to the quote.
+     */
+    pub async fn fetch_global_quote(
+        &self,
+        symbol: &str,
+        name: &str,
+    ) -> Result<SymbolQuoteExtended, AlphaVantageError> {
+        let url = self.prepare_url(&[("symbol", symbol)]);
+        let body = self.get_text(url).await?;
+
+        let deserialization_error = |message: String| {
+            self.response_error(AlphaVantageError::Deserialization {
+                endpoint: self.endpoint.name(),
+                message,
+                snippet: payload_snippet(&body),
+            })
+        };
+
+        let res: GlobalQuoteResponse =
+            serde_json::from_str(&body).map_err(|err| deserialization_error(err.to_string()))?;
+
+        // Unknown symbols get an empty quote, which isn't an error of the provider
+        res.quote
+            .into_quote(symbol, name)
+            .ok_or_else(|| AlphaVantageError::SymbolNotFound {
+                symbol: symbol.to_string(),
+            })
+    }
+
+    pub async fn fetch_earnings_calendar(&self) -> Result<Vec<Earning>, AlphaVantageError> {
+        let url = self.prepare_url(&[("horizon", "3month")]);
+        let csv_string = self.get_text(url).await?;
+
+        let mut rdr = ReaderBuilder::new()
+            .delimiter(b',')
+            .from_reader(csv_string.as_bytes());
+
+        rdr.deserialize()
+            .map(|result| {
+                result.map_err(|err| {
+                    self.response_error(AlphaVantageError::CsvParsingFailed(err.to_string()))
+                })
+            })
+            .collect()
+    }
+
+    /**
+     * Candles from the time series endpoint, which has to be set up with
+     * `Endpoint::time_series` for the resolution of the range.
+     */
+    pub async fn fetch_time_series(
+        &self,
+        symbol: &str,
+        range: &CandleRange,
+    ) -> Result<Vec<Candle>, AlphaVantageError> {
+        // The compact output only has the latest 100 entries
+        let output_size = match range.resolution {
+            Resolution::Day if range.from > unix_now() - COMPACT_DAILY_SECS => "compact",
+            _ => "full",
+        };
+        let interval = format!("{}min", range.resolution.as_str());
+
+        let mut query = vec![("symbol", symbol), ("outputsize", output_size)];
+        if range.resolution.is_intraday() {
+            query.push(("interval", &interval));
+        }
+        let url = self.prepare_url(&query);
+        let body = self.get_text(url).await?;
+
+        parse_time_series(&body, range).map_err(|message| {
+            self.response_error(AlphaVantageError::Deserialization {
+                endpoint: self.endpoint.name(),
+                message,
+                snippet: payload_snippet(&body),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn rate_limit(body: Value) -> Option<bool> {
+        match AlphaVantageError::from_message_body("quote", &body.to_string()) {
+            Some(AlphaVantageError::RateLimited { daily, .. }) => Some(daily),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn tells_the_per_minute_from_the_daily_rate_limit() {
+        let per_minute = json!({ "Note": "Thank you for using Alpha Vantage! Our standard API \
+            call frequency is 5 calls per minute and 500 calls per day." });
+        let daily = json!({ "Information": "Thank you for using Alpha Vantage! Our standard API \
+            rate limit is 25 requests per day." });
+        let other_note = json!({ "Note": "Please slow down." });
+
+        assert_eq!(rate_limit(per_minute), Some(false));
+        assert_eq!(rate_limit(daily), Some(true));
+        assert_eq!(rate_limit(other_note), Some(false));
+        assert_eq!(
+            rate_limit(json!({ "Information": "Invalid inputs." })),
+            None
+        );
+    }
 
-        let res = client.execute(req).await?;
-        let text = res.text().await.unwrap_or("ERORR".to_string());
+    #[test]
+    fn rate_limits_are_not_retried() {
+        let rate_limited = |daily| AlphaVantageError::RateLimited {
+            message: String::new(),
+            daily,
+        };
 
-        Ok(text)
+        assert_eq!(rate_limited(false).status(), None);
+        assert_eq!(rate_limited(true).status(), None);
+        assert!(!rate_limited(false).is_transient());
     }
 }
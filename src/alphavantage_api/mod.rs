@@ -1,4 +1,7 @@
+pub mod earnings_calendar;
+pub mod global_quote;
 pub mod lib;
 pub mod market_status;
 pub mod news_sentiment;
-pub mod earnings_calendar;
+pub mod provider;
+pub mod time_series;
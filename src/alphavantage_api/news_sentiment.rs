@@ -1,5 +1,12 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
 use serde::{Deserialize, Serialize};
 
+use crate::{candles::parse_date, finnhub_api::market_news::ArticleMarketNews};
+
 // Types for News Sentiment
 #[derive(Deserialize, Debug, Serialize)]
 struct TickerSentimentEntry {
@@ -23,6 +30,48 @@ pub struct NewsSentimentFeedEntry {
     ticker_sentiment: Vec<TickerSentimentEntry>,
 }
 
+impl NewsSentimentFeedEntry {
+    /**
+     * UNIX timestamp of `time_published`, which looks like "20230526T143000".
+     */
+    fn published_at(&self) -> Option<u32> {
+        let time = &self.time_published;
+        let date = format!(
+            "{}-{}-{}",
+            time.get(0..4)?,
+            time.get(4..6)?,
+            time.get(6..8)?
+        );
+        let hours: i64 = time.get(9..11)?.parse().ok()?;
+        let minutes: i64 = time.get(11..13)?.parse().ok()?;
+
+        u32::try_from(parse_date(&date)? + hours * 60 * 60 + minutes * 60).ok()
+    }
+}
+
+/**
+ * News in the shape of the Finnhub news, so both providers can serve the news routes.
+ * Alpha Vantage articles have no id, it is derived from the url instead.
+ */
+impl From<NewsSentimentFeedEntry> for ArticleMarketNews {
+    fn from(entry: NewsSentimentFeedEntry) -> Self {
+        let datetime = entry.published_at().unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        entry.url.hash(&mut hasher);
+
+        ArticleMarketNews {
+            headline: entry.title,
+            category: entry.category_within_source,
+            datetime,
+            id: hasher.finish() as u32,
+            image: entry.banner_image.unwrap_or_default(),
+            source: entry.source,
+            summary: entry.summary,
+            url: entry.url,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Serialize)]
 pub struct NewsSentimentResponse {
     pub feed: Vec<NewsSentimentFeedEntry>,
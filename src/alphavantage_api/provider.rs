@@ -0,0 +1,114 @@
+use async_trait::async_trait;
+
+use super::{
+    earnings_calendar::Earning,
+    lib::{AlphaVantageAPI, Endpoint},
+    market_status::MarketStatusInfo,
+    news_sentiment::NewsSentimentFeedEntry,
+};
+use crate::{
+    candles::{Candle, CandleRange},
+    error::{ApiError, Provider},
+    finnhub_api::{market_news::ArticleMarketNews, symbol_quote::SymbolQuoteExtended},
+    provider::{MarketDataProvider, Operation},
+};
+
+impl AlphaVantageAPI {
+    /**
+     * A copy of the client which requests the given endpoint.
+     */
+    fn with_endpoint(&self, endpoint: Endpoint) -> AlphaVantageAPI {
+        let mut av_api = self.clone();
+        av_api.endpoint(endpoint);
+        av_api
+    }
+}
+
+#[async_trait]
+impl MarketDataProvider for AlphaVantageAPI {
+    fn provider(&self) -> Provider {
+        Provider::AlphaVantage
+    }
+
+    fn operations(&self) -> &'static [Operation] {
+        &[
+            Operation::Quote,
+            Operation::MarketNews,
+            Operation::CompanyNews,
+            Operation::NewsSentiment,
+            Operation::MarketStatus,
+            Operation::EarningsCalendar,
+            Operation::Candles,
+        ]
+    }
+
+    async fn quote(&self, symbol: &str, name: &str) -> Result<SymbolQuoteExtended, ApiError> {
+        let quote = self
+            .with_endpoint(Endpoint::GlobalQuote)
+            .fetch_global_quote(symbol, name)
+            .await?;
+        Ok(quote)
+    }
+
+    async fn market_news(&self) -> Result<Vec<ArticleMarketNews>, ApiError> {
+        let feed = self
+            .with_endpoint(Endpoint::NewsSentiment)
+            .fetch_latest_news()
+            .await?;
+        Ok(feed.into_iter().map(ArticleMarketNews::from).collect())
+    }
+
+    async fn company_news(
+        &self,
+        symbol: &str,
+        time_from: &str,
+        time_to: &str,
+    ) -> Result<Vec<ArticleMarketNews>, ApiError> {
+        let feed = self
+            .with_endpoint(Endpoint::NewsSentiment)
+            .fetch_company_news(symbol, time_from, time_to)
+            .await?;
+        Ok(feed.into_iter().map(ArticleMarketNews::from).collect())
+    }
+
+    async fn news_sentiment(
+        &self,
+        ticker: Option<&str>,
+        time_from: &str,
+    ) -> Result<Vec<NewsSentimentFeedEntry>, ApiError> {
+        let av_api = self.with_endpoint(Endpoint::NewsSentiment);
+        let feed = match ticker {
+            Some(ticker) => {
+                av_api
+                    .fetch_news_sentiment_ticker(ticker.to_string(), time_from.to_string())
+                    .await?
+            }
+            None => av_api.fetch_news_sentiment(time_from.to_string()).await?,
+        };
+        Ok(feed)
+    }
+
+    async fn market_status(&self) -> Result<Vec<MarketStatusInfo>, ApiError> {
+        let markets = self
+            .with_endpoint(Endpoint::MarketStatus)
+            .fetch_market_status()
+            .await?;
+        Ok(markets)
+    }
+
+    async fn earnings_calendar(&self) -> Result<Vec<Earning>, ApiError> {
+        let earnings = self
+            .with_endpoint(Endpoint::EarningsCalendar)
+            .fetch_earnings_calendar()
+            .await?;
+        Ok(earnings)
+    }
+
+    async fn candles(&self, symbol: &str, range: &CandleRange) -> Result<Vec<Candle>, ApiError> {
+        let candles = self
+            .with_endpoint(Endpoint::time_series(range.resolution))
+            .fetch_time_series(symbol, range)
+            .await?;
+        Ok(candles)
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::candles::{days_from_civil, parse_date, Candle, CandleRange};
+
+const SECONDS_PER_HOUR: i64 = 60 * 60;
+
+/**
+ * A single entry of the `Time Series (...)` object, keyed by date or date and time.
+ */
+#[derive(Deserialize, Debug)]
+struct TimeSeriesEntry {
+    #[serde(rename = "1. open")]
+    open: String,
+    #[serde(rename = "2. high")]
+    high: String,
+    #[serde(rename = "3. low")]
+    low: String,
+    #[serde(rename = "4. close")]
+    close: String,
+    #[serde(rename = "5. volume")]
+    volume: String,
+}
+
+/**
+ * Parses a TIME_SERIES_* response into candles within the range, sorted by time.
+ *
+ * The series is the only object besides "Meta Data", its name depends on the function, e.g.
+ * "Time Series (Daily)", "Time Series (5min)" or "Weekly Time Series". Intraday times are
+ * US/Eastern, dates are taken as the start of the day in UTC.
+ */
+pub fn parse_time_series(body: &str, range: &CandleRange) -> Result<Vec<Candle>, String> {
+    let response: HashMap<String, Value> =
+        serde_json::from_str(body).map_err(|err| err.to_string())?;
+
+    let series = response
+        .into_iter()
+        .find(|(key, value)| key != "Meta Data" && value.is_object())
+        .map(|(_, value)| value)
+        .ok_or("the response has no time series")?;
+    let series: HashMap<String, TimeSeriesEntry> =
+        serde_json::from_value(series).map_err(|err| err.to_string())?;
+
+    let mut candles = Vec::with_capacity(series.len());
+    for (time, entry) in series {
+        let timestamp =
+            parse_timestamp(&time).ok_or_else(|| format!("invalid time series key '{time}'"))?;
+        if !range.contains(timestamp) {
+            continue;
+        }
+
+        let number = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("invalid number '{value}' at '{time}'"))
+        };
+        candles.push(Candle {
+            timestamp,
+            open: number(&entry.open)?,
+            high: number(&entry.high)?,
+            low: number(&entry.low)?,
+            close: number(&entry.close)?,
+            volume: number(&entry.volume)?,
+        });
+    }
+
+    candles.sort_by_key(|candle| candle.timestamp);
+    Ok(candles)
+}
+
+/**
+ * "2023-06-30" or "2023-06-30 15:55:00" (US/Eastern) as UNIX timestamp.
+ */
+fn parse_timestamp(time: &str) -> Option<i64> {
+    let (date, clock) = match time.split_once(' ') {
+        Some((date, clock)) => (date, Some(clock)),
+        None => (time, None),
+    };
+    let day_start = parse_date(date)?;
+
+    let Some(clock) = clock else {
+        return Some(day_start);
+    };
+
+    let mut parts = clock.splitn(3, ':');
+    let hours: i64 = parts.next()?.parse().ok()?;
+    let minutes: i64 = parts.next()?.parse().ok()?;
+    let seconds: i64 = parts.next().unwrap_or("0").parse().ok()?;
+    let local = day_start + hours * SECONDS_PER_HOUR + minutes * 60 + seconds;
+
+    Some(local + eastern_utc_offset_hours(day_start, hours) * SECONDS_PER_HOUR)
+}
+
+/**
+ * Hours US/Eastern is behind UTC. Daylight saving time starts on the second Sunday of March
+ * and ends on the first Sunday of November, both at 2:00 local time.
+ */
+fn eastern_utc_offset_hours(day_start: i64, hour: i64) -> i64 {
+    let day = day_start.div_euclid(24 * SECONDS_PER_HOUR);
+    let year = year_of_day(day);
+
+    let nth_sunday = |month: u32, n: i64| {
+        let first = days_from_civil(year, month, 1);
+        // 1970-01-01 was a Thursday, 0 is Sunday
+        let weekday = (first + 4).rem_euclid(7);
+        first + (7 - weekday) % 7 + (n - 1) * 7
+    };
+    let dst_start = nth_sunday(3, 2);
+    let dst_end = nth_sunday(11, 1);
+
+    let after_start = day > dst_start || (day == dst_start && hour >= 2);
+    let before_end = day < dst_end || (day == dst_end && hour < 2);
+
+    if after_start && before_end {
+        4
+    } else {
+        5
+    }
+}
+
+fn year_of_day(day: i64) -> i64 {
+    // Starts with an estimate which is off by at most one year
+    let mut year = 1970 + day.div_euclid(365);
+    while days_from_civil(year, 1, 1) > day {
+        year -= 1;
+    }
+    while days_from_civil(year + 1, 1, 1) <= day {
+        year += 1;
+    }
+    year
+}
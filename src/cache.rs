@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{Bytes, Full},
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{provider::DATA_PROVIDER_HEADER, AppState};
+
+/**
+ * Query parameter which skips the cache lookup, e.g. `/api/v1/quotes/djia?no_cache=true`.
+ */
+const BYPASS_QUERY_PARAM: &str = "no_cache";
+
+/**
+ * Paths (relative to `/api/v1`) which are never cached. The quotes of a watchlist have to
+ * follow changes of the watchlist right away.
+ */
+const UNCACHED_PATHS: [&str; 1] = ["/quotes/watchlist/"];
+
+/**
+ * Time to live for cached responses, keyed by the first path segment after `/api/v1`
+ * (e.g. "quotes" or "market-status").
+ */
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    ttls: HashMap<String, Duration>,
+    max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        let ttls = [
+            ("market-news", Duration::from_secs(5 * 60)),
+            ("company-news", Duration::from_secs(15 * 60)),
+            ("market-status", Duration::from_secs(5 * 60)),
+            ("news-sentiment", Duration::from_secs(60 * 60)),
+            ("news-sentiment-ticker", Duration::from_secs(60 * 60)),
+            ("social-sentiment", Duration::from_secs(60 * 60)),
+            ("quotes", Duration::from_secs(15)),
+            ("quote", Duration::from_secs(15)),
+            ("candles", Duration::from_secs(5 * 60)),
+            ("indicators", Duration::from_secs(15 * 60)),
+            ("company-profile", Duration::from_secs(24 * 60 * 60)),
+            ("earnings-calendar", Duration::from_secs(12 * 60 * 60)),
+        ]
+        .into_iter()
+        .map(|(endpoint, ttl)| (endpoint.to_string(), ttl))
+        .collect();
+
+        CacheConfig {
+            ttls,
+            max_entries: 1024,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn endpoints(&self) -> Vec<String> {
+        self.ttls.keys().cloned().collect()
+    }
+
+    /**
+     * Overrides the TTL of an endpoint. A TTL of zero disables caching for it.
+     */
+    pub fn ttl(&mut self, endpoint: &str, ttl: Duration) -> &mut CacheConfig {
+        self.ttls.insert(endpoint.to_string(), ttl);
+        self
+    }
+
+    fn ttl_for(&self, endpoint: &str) -> Option<Duration> {
+        self.ttls
+            .get(endpoint)
+            .copied()
+            .filter(|ttl| !ttl.is_zero())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    content_type: Option<HeaderValue>,
+    data_provider: Option<HeaderValue>,
+    body: Bytes,
+    stored_at: Instant,
+    ttl: Duration,
+}
+
+impl CachedResponse {
+    fn is_fresh(&self) -> bool {
+        self.stored_at.elapsed() < self.ttl
+    }
+}
+
+/**
+ * In-memory cache for successful GET responses of the API routes.
+ */
+#[derive(Debug)]
+pub struct ResponseCache {
+    config: CacheConfig,
+    entries: Mutex<HashMap<String, CachedResponse>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/**
+ * Lookups of cacheable requests, bypassed lookups are not counted.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl ResponseCache {
+    pub fn new(config: CacheConfig) -> ResponseCache {
+        ResponseCache {
+            config,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key).filter(|entry| entry.is_fresh()).cloned()
+    }
+
+    fn insert(&self, key: String, entry: CachedResponse) {
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= self.config.max_entries {
+            entries.retain(|_, entry| entry.is_fresh());
+        }
+
+        if entries.len() >= self.config.max_entries {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(key, _)| key.clone());
+
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(key, entry);
+    }
+}
+
+enum CacheStatus {
+    Hit,
+    Miss,
+    Bypass,
+}
+
+impl CacheStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hit => "HIT",
+            Self::Miss => "MISS",
+            Self::Bypass => "BYPASS",
+        }
+    }
+}
+
+/**
+ * Middleware serving GET requests from the `ResponseCache` of the `AppState`.
+ *
+ * The cache is bypassed with a `Cache-Control: no-cache` request header or the `no_cache`
+ * query parameter. The fresh response replaces the cached one in that case.
+ */
+pub async fn cache_responses<B>(
+    State(state): State<Arc<AppState>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let path = req.uri().path().to_string();
+    if UNCACHED_PATHS.iter().any(|prefix| path.starts_with(prefix)) {
+        let mut response = next.run(req).await;
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        return response;
+    }
+    let endpoint = path.trim_start_matches('/').split('/').next().unwrap_or("");
+
+    let ttl = match state.cache.config.ttl_for(endpoint) {
+        Some(ttl) if req.method() == Method::GET => ttl,
+        _ => return next.run(req).await,
+    };
+
+    let bypass = is_bypass_requested(req.headers(), req.uri().query());
+    let key = cache_key(&path, req.uri().query());
+
+    if !bypass {
+        if let Some(entry) = state.cache.get(&key) {
+            state.cache.hits.fetch_add(1, Ordering::Relaxed);
+            return cached_response(&entry, CacheStatus::Hit);
+        }
+        state.cache.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let response = next.run(req).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let entry = CachedResponse {
+        content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+        data_provider: parts.headers.get(DATA_PROVIDER_HEADER).cloned(),
+        body,
+        stored_at: Instant::now(),
+        ttl,
+    };
+    state.cache.insert(key, entry.clone());
+
+    let status = if bypass {
+        CacheStatus::Bypass
+    } else {
+        CacheStatus::Miss
+    };
+    cached_response(&entry, status)
+}
+
+fn is_bypass_requested(headers: &HeaderMap, query: Option<&str>) -> bool {
+    let no_cache_header = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("no-cache"));
+
+    let no_cache_param = url::form_urlencoded::parse(query.unwrap_or("").as_bytes())
+        .any(|(key, value)| key == BYPASS_QUERY_PARAM && value != "false");
+
+    no_cache_header || no_cache_param
+}
+
+/**
+ * Builds the cache key from the path and the query parameters sorted by name, so
+ * `?a=1&b=2` and `?b=2&a=1` share one entry.
+ */
+fn cache_key(path: &str, query: Option<&str>) -> String {
+    let mut params: Vec<(String, String)> =
+        url::form_urlencoded::parse(query.unwrap_or("").as_bytes())
+            .filter(|(key, _)| key != BYPASS_QUERY_PARAM)
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+
+    if params.is_empty() {
+        return path.to_string();
+    }
+
+    params.sort();
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+
+    format!("{path}?{query}")
+}
+
+fn cached_response(entry: &CachedResponse, status: CacheStatus) -> Response {
+    let age = entry.stored_at.elapsed();
+    let max_age = entry.ttl.saturating_sub(age);
+
+    let mut response = Full::new(entry.body.clone()).into_response();
+    let headers = response.headers_mut();
+
+    if let Some(content_type) = &entry.content_type {
+        headers.insert(header::CONTENT_TYPE, content_type.clone());
+    }
+    if let Some(data_provider) = &entry.data_provider {
+        headers.insert(DATA_PROVIDER_HEADER, data_provider.clone());
+    }
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_str(&format!("public, max-age={}", max_age.as_secs())).unwrap(),
+    );
+    headers.insert(header::AGE, HeaderValue::from(age.as_secs()));
+    headers.insert("x-cache", HeaderValue::from_static(status.as_str()));
+
+    response
+}
@@ -0,0 +1,392 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ApiError, Provider};
+
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+/**
+ * Longest range which can be requested for intraday resolutions.
+ */
+const MAX_INTRADAY_DAYS: i64 = 31;
+/**
+ * Years of the accepted dates, keeps the date math far from overflowing
+ */
+const YEARS: std::ops::RangeInclusive<i64> = 1970..=9999;
+/**
+ * 9999-12-31T23:59:59Z, the end of the last accepted date
+ */
+const MAX_TIMESTAMP: i64 = 253_402_300_799;
+
+/**
+ * Candle resolutions, named like the Finnhub `resolution` parameter.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Minute1,
+    Minute5,
+    Minute15,
+    Minute30,
+    Minute60,
+    Day,
+    Week,
+    Month,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 8] = [
+        Self::Minute1,
+        Self::Minute5,
+        Self::Minute15,
+        Self::Minute30,
+        Self::Minute60,
+        Self::Day,
+        Self::Week,
+        Self::Month,
+    ];
+
+    pub fn parse(resolution: &str) -> Option<Resolution> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str().eq_ignore_ascii_case(resolution))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Minute1 => "1",
+            Self::Minute5 => "5",
+            Self::Minute15 => "15",
+            Self::Minute30 => "30",
+            Self::Minute60 => "60",
+            Self::Day => "D",
+            Self::Week => "W",
+            Self::Month => "M",
+        }
+    }
+
+    pub fn is_intraday(&self) -> bool {
+        !matches!(self, Self::Day | Self::Week | Self::Month)
+    }
+
+    /**
+     * Range used if the request has no `from`.
+     */
+    fn default_days(&self) -> i64 {
+        match self {
+            Self::Minute1 | Self::Minute5 => 1,
+            Self::Minute15 | Self::Minute30 | Self::Minute60 => 5,
+            Self::Day => 365,
+            Self::Week => 5 * 365,
+            Self::Month => 10 * 365,
+        }
+    }
+}
+
+impl Serialize for Resolution {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/**
+ * A single OHLC candle. `timestamp` is the UNIX time (seconds) the candle starts at.
+ */
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Candle {
+    pub timestamp: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+/**
+ * Candles of a symbol sorted by time, the same shape for every provider.
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct CandleSeries {
+    pub symbol: String,
+    pub resolution: Resolution,
+    pub from: i64,
+    pub to: i64,
+    pub provider: Provider,
+    pub candles: Vec<Candle>,
+}
+
+/*
+ * ?resolution=D&from=2023-01-01&to=2023-06-30, `from` and `to` may also be UNIX timestamps
+ */
+#[derive(Deserialize)]
+pub struct QueryCandles {
+    pub resolution: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/**
+ * Validated candle request, `from` and `to` are UNIX timestamps (seconds, inclusive).
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct CandleRange {
+    pub resolution: Resolution,
+    pub from: i64,
+    pub to: i64,
+}
+
+impl CandleRange {
+    /**
+     * Validates the query. Dates are whole days, so `to=2023-06-30` includes that day.
+     * Defaults to daily candles of the last year until now.
+     */
+    pub fn from_query(query: &QueryCandles) -> Result<CandleRange, ApiError> {
+        let now = unix_now();
+
+        let resolution = match &query.resolution {
+            Some(resolution) => Resolution::parse(resolution).ok_or_else(|| {
+                let all: Vec<&str> = Resolution::ALL.iter().map(Resolution::as_str).collect();
+                ApiError::InvalidInput(format!(
+                    "Invalid resolution '{resolution}'. Try one of '{}'.",
+                    all.join("', '")
+                ))
+            })?,
+            None => Resolution::Day,
+        };
+
+        let to = match &query.to {
+            Some(to) => parse_time(to, true)?,
+            None => now,
+        };
+        let from = match &query.from {
+            Some(from) => parse_time(from, false)?,
+            None => to - resolution.default_days() * SECONDS_PER_DAY,
+        };
+
+        if from > to {
+            return Err(ApiError::InvalidInput(
+                "'from' must not be after 'to'.".to_string(),
+            ));
+        }
+        if from > now {
+            return Err(ApiError::InvalidInput(
+                "'from' must not be in the future.".to_string(),
+            ));
+        }
+        if resolution.is_intraday() && to - from > MAX_INTRADAY_DAYS * SECONDS_PER_DAY {
+            return Err(ApiError::InvalidInput(format!(
+                "Intraday candles can be requested for at most {MAX_INTRADAY_DAYS} days."
+            )));
+        }
+
+        Ok(CandleRange {
+            resolution,
+            from,
+            to: to.min(now),
+        })
+    }
+
+    pub fn contains(&self, timestamp: i64) -> bool {
+        (self.from..=self.to).contains(&timestamp)
+    }
+}
+
+/**
+ * Parses a UNIX timestamp or a yyyy-mm-dd date, which is the start of the day (or its end
+ * with `end_of_day`) in UTC.
+ */
+fn parse_time(value: &str, end_of_day: bool) -> Result<i64, ApiError> {
+    let invalid = || {
+        ApiError::InvalidInput(format!(
+            "Invalid time '{value}'. Use yyyy-mm-dd or a UNIX timestamp."
+        ))
+    };
+
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        return value
+            .parse()
+            .ok()
+            .filter(|timestamp| *timestamp <= MAX_TIMESTAMP)
+            .ok_or_else(invalid);
+    }
+
+    let start = parse_date(value).ok_or_else(invalid)?;
+    Ok(if end_of_day {
+        start + SECONDS_PER_DAY - 1
+    } else {
+        start
+    })
+}
+
+/**
+ * UNIX timestamp of the start (UTC) of a yyyy-mm-dd date between the years 1970 and 9999.
+ */
+pub fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+
+    if !YEARS.contains(&year)
+        || !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+    {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * SECONDS_PER_DAY)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/**
+ * Days since 1970-01-01 of a date in the proleptic Gregorian calendar,
+ * see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+ */
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/**
+ * The yyyy-mm-dd date (UTC) of a UNIX timestamp, the inverse of `days_from_civil`,
+ * see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+ */
+pub fn format_date(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(SECONDS_PER_DAY) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(resolution: Option<&str>, from: Option<&str>, to: Option<&str>) -> QueryCandles {
+        QueryCandles {
+            resolution: resolution.map(str::to_string),
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2023-06-30"), Some(1_688_083_200));
+        assert_eq!(
+            parse_date("9999-12-31"),
+            Some(MAX_TIMESTAMP + 1 - SECONDS_PER_DAY)
+        );
+    }
+
+    #[test]
+    fn parses_leap_days() {
+        assert_eq!(parse_date("2024-02-29"), Some(1_709_164_800));
+        assert_eq!(parse_date("2000-02-29"), Some(951_782_400));
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("2100-02-29"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        for date in [
+            "2023-13-01",
+            "2023-00-10",
+            "2023-04-31",
+            "2023-01-00",
+            "2023-01",
+            "2023-01-xx",
+            "",
+            "1969-12-31",
+            "10000-01-01",
+            "100000000000000-01-01",
+            "-2023-01-01",
+        ] {
+            assert_eq!(parse_date(date), None, "{date}");
+        }
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(1_709_164_800 + 12 * 60 * 60), "2024-02-29");
+        assert_eq!(format_date(MAX_TIMESTAMP), "9999-12-31");
+
+        for date in ["2000-02-29", "2023-12-31", "2024-03-01", "2100-02-28"] {
+            assert_eq!(format_date(parse_date(date).unwrap()), date);
+        }
+    }
+
+    #[test]
+    fn candle_range_of_dates_includes_the_last_day() {
+        let range =
+            CandleRange::from_query(&query(Some("d"), Some("2023-01-01"), Some("2023-06-30")))
+                .unwrap();
+
+        assert_eq!(range.resolution, Resolution::Day);
+        assert_eq!(range.from, parse_date("2023-01-01").unwrap());
+        assert_eq!(range.to, parse_date("2023-07-01").unwrap() - 1);
+    }
+
+    #[test]
+    fn candle_range_defaults_to_a_year_of_daily_candles() {
+        let range = CandleRange::from_query(&query(None, None, None)).unwrap();
+
+        assert_eq!(range.resolution, Resolution::Day);
+        assert_eq!(range.to - range.from, 365 * SECONDS_PER_DAY);
+    }
+
+    #[test]
+    fn candle_range_rejects_invalid_queries() {
+        for (resolution, from, to) in [
+            (Some("2"), None, None),
+            (None, Some("2023-06-30"), Some("2023-01-01")),
+            (None, Some("2023-02-30"), None),
+            (None, Some("100000000000000-01-01"), None),
+            (None, None, Some("99999999999999999999")),
+            (None, None, Some("253402300800")),
+            (Some("5"), Some("2023-01-01"), Some("2023-03-01")),
+        ] {
+            let result = CandleRange::from_query(&query(resolution, from, to));
+            assert!(
+                matches!(result, Err(ApiError::InvalidInput(_))),
+                "{resolution:?} {from:?} {to:?}"
+            );
+        }
+    }
+}
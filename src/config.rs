@@ -0,0 +1,448 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    cache::CacheConfig,
+    error::Provider,
+    http_client::HttpClientConfig,
+    indices::IndexError,
+    provider::{parse_priority, Operation},
+    watchlists::WatchlistError,
+};
+use url::Url;
+
+/**
+ * Env var with the path of an optional TOML file, its values are overridden by env vars.
+ */
+pub const CONFIG_FILE_VAR: &str = "CONFIG_FILE";
+
+const DEFAULT_FINNHUB_WS_URL: &str = "wss://ws.finnhub.io";
+const DEFAULT_WATCHLISTS_PATH: &str = "watchlists.json";
+const DEFAULT_LISTEN_ADDR: &str = "0.0.0.0:8000";
+const DEFAULT_RATE_LIMIT_MAX_WAIT_SECS: u64 = 30;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Missing required setting {0}")]
+    Missing(&'static str),
+    #[error("Invalid value '{value}' for {key}: {message}")]
+    Invalid {
+        key: String,
+        value: String,
+        message: String,
+    },
+    #[error("Failed reading the config file {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Failed parsing the config file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+}
+
+/**
+ * Errors setting up the service from a valid config, or serving it.
+ */
+#[derive(thiserror::Error, Debug)]
+pub enum StartupError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error("Failed building the HTTP client: {0}")]
+    HttpClient(#[from] reqwest::Error),
+    #[error(transparent)]
+    Indices(#[from] IndexError),
+    #[error("Failed loading the watchlists: {0}")]
+    Watchlists(#[from] WatchlistError),
+    #[error("Server error: {0}")]
+    Server(#[from] hyper::Error),
+}
+
+/**
+ * Settings of the service. They are read from env vars, a flat TOML file or the Shuttle
+ * secrets, with the same keys everywhere, e.g. `FINNHUB_API_TOKEN` or
+ * `HTTP_REQUEST_TIMEOUT_SECS`. Only the API tokens are required.
+ *
+ * Not `Debug`, so the tokens don't end up in logs.
+ */
+#[derive(Clone)]
+pub struct Config {
+    pub finnhub_api_token: String,
+    pub alphavantage_api_token: String,
+    pub finnhub_base_url: Option<Url>,
+    pub alphavantage_base_url: Option<Url>,
+    /**
+     * Finnhub trades WebSocket, the token is added as query parameter
+     */
+    pub finnhub_ws_url: Url,
+    pub finnhub_calls_per_minute: Option<u32>,
+    pub finnhub_rate_limit_max_wait: Duration,
+    pub alphavantage_calls_per_day: Option<u32>,
+    /**
+     * Attempts of an upstream call including the first one, the retry policy's by default
+     */
+    pub upstream_max_attempts: Option<u32>,
+    pub http_client: HttpClientConfig,
+    pub cache: CacheConfig,
+    pub indices_dir: Option<PathBuf>,
+    pub watchlists_path: PathBuf,
+    pub provider_priority: Option<Vec<Provider>>,
+    pub operation_priorities: Vec<(Operation, Vec<Provider>)>,
+    /**
+     * Address of the standalone server, Shuttle binds its own
+     */
+    pub listen_addr: SocketAddr,
+}
+
+impl Config {
+    /**
+     * Reads the config from the env vars and the TOML file at `CONFIG_FILE`, if set.
+     * Env vars take precedence over the file.
+     */
+    pub fn load() -> Result<Config, ConfigError> {
+        let file = match std::env::var_os(CONFIG_FILE_VAR) {
+            Some(path) => read_toml(Path::new(&path))?,
+            None => HashMap::new(),
+        };
+
+        Config::from_lookup(|key| std::env::var(key).ok().or_else(|| file.get(key).cloned()))
+    }
+
+    /**
+     * Reads the config from any key-value source, e.g. the Shuttle secrets.
+     * Empty values count as missing.
+     */
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
+        let settings = Settings { lookup };
+        let finnhub_api_token = settings.required("FINNHUB_API_TOKEN")?;
+        let alphavantage_api_token = settings.required("ALPHA_VANTAGE_API_TOKEN")?;
+
+        let mut http_client = HttpClientConfig::default();
+        if let Some(secs) = settings.positive::<u64>("HTTP_CONNECT_TIMEOUT_SECS")? {
+            http_client.connect_timeout = Duration::from_secs(secs);
+        }
+        if let Some(secs) = settings.positive::<u64>("HTTP_REQUEST_TIMEOUT_SECS")? {
+            http_client.request_timeout = Duration::from_secs(secs);
+        }
+        if let Some(max_idle) = settings.parse("HTTP_POOL_MAX_IDLE_PER_HOST")? {
+            http_client.pool_max_idle_per_host = max_idle;
+        }
+
+        // Cache TTLs can be overridden per endpoint, e.g. CACHE_TTL_MARKET_STATUS=600 (seconds)
+        let mut cache = CacheConfig::default();
+        for endpoint in cache.endpoints() {
+            let key = format!("CACHE_TTL_{}", endpoint.to_uppercase().replace('-', "_"));
+            if let Some(ttl) = settings.parse::<u64>(&key)? {
+                cache.ttl(&endpoint, Duration::from_secs(ttl));
+            }
+        }
+
+        let mut operation_priorities = Vec::new();
+        for operation in Operation::ALL {
+            let key = format!("PROVIDER_PRIORITY_{}", operation.as_str().to_uppercase());
+            if let Some(priority) = settings.priority(&key)? {
+                operation_priorities.push((operation, priority));
+            }
+        }
+
+        let finnhub_ws_url = settings
+            .parse::<Url>("FINNHUB_WS_URL")?
+            .unwrap_or_else(|| DEFAULT_FINNHUB_WS_URL.parse().unwrap());
+        let listen_addr = settings
+            .parse("LISTEN_ADDR")?
+            .unwrap_or_else(|| DEFAULT_LISTEN_ADDR.parse().unwrap());
+
+        Ok(Config {
+            finnhub_api_token,
+            alphavantage_api_token,
+            finnhub_base_url: settings.parse("FINNHUB_BASE_URL")?,
+            alphavantage_base_url: settings.parse("ALPHA_VANTAGE_BASE_URL")?,
+            finnhub_ws_url,
+            finnhub_calls_per_minute: settings.positive("FINNHUB_CALLS_PER_MINUTE")?,
+            finnhub_rate_limit_max_wait: Duration::from_secs(
+                settings
+                    .parse("FINNHUB_RATE_LIMIT_MAX_WAIT_SECS")?
+                    .unwrap_or(DEFAULT_RATE_LIMIT_MAX_WAIT_SECS),
+            ),
+            alphavantage_calls_per_day: settings.parse("ALPHA_VANTAGE_CALLS_PER_DAY")?,
+            upstream_max_attempts: settings.positive("UPSTREAM_MAX_ATTEMPTS")?,
+            http_client,
+            cache,
+            indices_dir: settings.get("INDICES_DIR").map(PathBuf::from),
+            watchlists_path: settings
+                .get("WATCHLISTS_PATH")
+                .unwrap_or_else(|| DEFAULT_WATCHLISTS_PATH.to_string())
+                .into(),
+            provider_priority: settings.priority("PROVIDER_PRIORITY")?,
+            operation_priorities,
+            listen_addr,
+        })
+    }
+}
+
+struct Settings<F> {
+    lookup: F,
+}
+
+impl<F: Fn(&str) -> Option<String>> Settings<F> {
+    fn get(&self, key: &str) -> Option<String> {
+        (self.lookup)(key)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn required(&self, key: &'static str) -> Result<String, ConfigError> {
+        self.get(key).ok_or(ConfigError::Missing(key))
+    }
+
+    fn invalid(key: &str, value: String, message: impl Display) -> ConfigError {
+        ConfigError::Invalid {
+            key: key.to_string(),
+            value,
+            message: message.to_string(),
+        }
+    }
+
+    fn parse<T>(&self, key: &str) -> Result<Option<T>, ConfigError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let Some(value) = self.get(key) else {
+            return Ok(None);
+        };
+        match value.parse() {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(err) => Err(Self::invalid(key, value, err)),
+        }
+    }
+
+    /**
+     * A number greater than zero.
+     */
+    fn positive<T>(&self, key: &str) -> Result<Option<T>, ConfigError>
+    where
+        T: FromStr + Default + PartialEq + Display,
+        T::Err: Display,
+    {
+        match self.parse::<T>(key)? {
+            Some(number) if number == T::default() => Err(Self::invalid(
+                key,
+                number.to_string(),
+                "must be greater than 0",
+            )),
+            number => Ok(number),
+        }
+    }
+
+    fn priority(&self, key: &str) -> Result<Option<Vec<Provider>>, ConfigError> {
+        let Some(value) = self.get(key) else {
+            return Ok(None);
+        };
+        match parse_priority(&value) {
+            Ok(priority) if priority.is_empty() => {
+                Err(Self::invalid(key, value, "needs at least one provider"))
+            }
+            Ok(priority) => Ok(Some(priority)),
+            Err(err) => Err(Self::invalid(key, value, err)),
+        }
+    }
+}
+
+fn read_toml(path: &Path) -> Result<HashMap<String, String>, ConfigError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    parse_toml(&contents).map_err(|source| ConfigError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/**
+ * Flat TOML file of `KEY = value` pairs like Shuttle's `Secrets.toml`.
+ */
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct ConfigFile {
+    values: HashMap<String, ConfigFileValue>,
+}
+
+/**
+ * The values are read as strings like the env vars, tables and arrays are not supported.
+ */
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConfigFileValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl Display for ConfigFileValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String(value) => value.fmt(f),
+            Self::Integer(value) => value.fmt(f),
+            Self::Float(value) => value.fmt(f),
+            Self::Boolean(value) => value.fmt(f),
+        }
+    }
+}
+
+fn parse_toml(contents: &str) -> Result<HashMap<String, String>, toml::de::Error> {
+    let file: ConfigFile = toml::from_str(contents)?;
+
+    Ok(file
+        .values
+        .into_iter()
+        .map(|(key, value)| (key, value.to_string()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(settings: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let settings: HashMap<String, String> = [
+            ("FINNHUB_API_TOKEN", "finnhub-token"),
+            ("ALPHA_VANTAGE_API_TOKEN", "alphavantage-token"),
+        ]
+        .iter()
+        .chain(settings)
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+        Config::from_lookup(|key| settings.get(key).cloned())
+    }
+
+    #[test]
+    fn reads_the_settings() {
+        let config = config(&[
+            ("HTTP_REQUEST_TIMEOUT_SECS", "10"),
+            ("ALPHA_VANTAGE_CALLS_PER_DAY", "500"),
+            ("CACHE_TTL_MARKET_STATUS", "0"),
+            ("PROVIDER_PRIORITY", "alphavantage, finnhub"),
+            ("PROVIDER_PRIORITY_QUOTE", "finnhub"),
+            ("FINNHUB_BASE_URL", "http://localhost:9000/api/v1"),
+            ("LISTEN_ADDR", "127.0.0.1:3000"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.finnhub_api_token, "finnhub-token");
+        assert_eq!(config.http_client.request_timeout, Duration::from_secs(10));
+        assert_eq!(config.alphavantage_calls_per_day, Some(500));
+        assert_eq!(config.finnhub_calls_per_minute, None);
+        assert_eq!(
+            config.provider_priority,
+            Some(vec![Provider::AlphaVantage, Provider::Finnhub])
+        );
+        assert_eq!(
+            config.operation_priorities,
+            vec![(Operation::Quote, vec![Provider::Finnhub])]
+        );
+        assert_eq!(
+            config.finnhub_base_url.unwrap().as_str(),
+            "http://localhost:9000/api/v1"
+        );
+        assert_eq!(config.finnhub_ws_url.as_str(), "wss://ws.finnhub.io/");
+        assert_eq!(config.watchlists_path, PathBuf::from("watchlists.json"));
+        assert_eq!(config.listen_addr, "127.0.0.1:3000".parse().unwrap());
+    }
+
+    #[test]
+    fn fails_without_the_api_tokens() {
+        let result = Config::from_lookup(|key| {
+            (key == "FINNHUB_API_TOKEN").then(|| "finnhub-token".to_string())
+        });
+        assert!(matches!(
+            result.err(),
+            Some(ConfigError::Missing("ALPHA_VANTAGE_API_TOKEN"))
+        ));
+
+        let result = config(&[("FINNHUB_API_TOKEN", " ")]);
+        assert!(matches!(
+            result.err(),
+            Some(ConfigError::Missing("FINNHUB_API_TOKEN"))
+        ));
+    }
+
+    #[test]
+    fn fails_on_invalid_values() {
+        let invalid_key = |settings: &[(&str, &str)]| match config(settings).err() {
+            Some(ConfigError::Invalid { key, .. }) => key,
+            _ => panic!("expected {settings:?} to be invalid"),
+        };
+
+        assert_eq!(
+            invalid_key(&[("HTTP_REQUEST_TIMEOUT_SECS", "ten")]),
+            "HTTP_REQUEST_TIMEOUT_SECS"
+        );
+        assert_eq!(
+            invalid_key(&[("UPSTREAM_MAX_ATTEMPTS", "0")]),
+            "UPSTREAM_MAX_ATTEMPTS"
+        );
+        assert_eq!(
+            invalid_key(&[("PROVIDER_PRIORITY", "finnhub,yahoo")]),
+            "PROVIDER_PRIORITY"
+        );
+        assert_eq!(
+            invalid_key(&[("PROVIDER_PRIORITY_CANDLES", ",")]),
+            "PROVIDER_PRIORITY_CANDLES"
+        );
+        assert_eq!(
+            invalid_key(&[("ALPHA_VANTAGE_BASE_URL", "localhost")]),
+            "ALPHA_VANTAGE_BASE_URL"
+        );
+    }
+
+    #[test]
+    fn parses_flat_toml() {
+        let values = parse_toml(
+            r#"
+            # Shuttle's Secrets.toml works as well
+            FINNHUB_API_TOKEN = "abc\"123"
+            ALPHA_VANTAGE_API_TOKEN = 'C:\tokens' # literal string
+            HTTP_REQUEST_TIMEOUT_SECS = 10
+            CACHE_ENABLED = true
+            "WATCHLISTS_PATH" = "/data/watchlists.json"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(values["FINNHUB_API_TOKEN"], "abc\"123");
+        assert_eq!(values["ALPHA_VANTAGE_API_TOKEN"], "C:\\tokens");
+        assert_eq!(values["HTTP_REQUEST_TIMEOUT_SECS"], "10");
+        assert_eq!(values["CACHE_ENABLED"], "true");
+        assert_eq!(values["WATCHLISTS_PATH"], "/data/watchlists.json");
+    }
+
+    #[test]
+    fn rejects_unsupported_toml() {
+        for contents in [
+            "A = 1\n[server]\nB = 2",
+            "A = \"unterminated",
+            "A = unquoted",
+            "A = [1, 2]",
+            "A = 1\nA = 2",
+            "A = \"x\" y",
+        ] {
+            assert!(
+                parse_toml(contents).is_err(),
+                "expected {contents:?} to fail"
+            );
+        }
+    }
+}
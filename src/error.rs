@@ -0,0 +1,179 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    alphavantage_api::lib::AlphaVantageError, finnhub_api::lib::FinnhubError, logging,
+    provider::Operation, watchlists::WatchlistError,
+};
+
+/**
+ * The upstream data provider an error or response originated from.
+ */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    Finnhub,
+    AlphaVantage,
+}
+
+impl Provider {
+    pub const ALL: [Provider; 2] = [Self::Finnhub, Self::AlphaVantage];
+
+    pub fn parse(provider: &str) -> Option<Provider> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str().eq_ignore_ascii_case(provider.trim()))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Finnhub => "finnhub",
+            Self::AlphaVantage => "alphavantage",
+        }
+    }
+}
+
+/**
+ * Error type returned by every route handler.
+ *
+ * Gets rendered as `{ "code": ..., "message": ..., "provider": ... }` with a matching status code.
+ */
+#[derive(thiserror::Error, Debug)]
+pub enum ApiError {
+    #[error(transparent)]
+    Finnhub(#[from] FinnhubError),
+    #[error(transparent)]
+    AlphaVantage(#[from] AlphaVantageError),
+    #[error(transparent)]
+    Watchlist(#[from] WatchlistError),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("No configured data provider supports {0}")]
+    Unsupported(Operation),
+}
+
+impl ApiError {
+    pub fn provider(&self) -> Option<Provider> {
+        match self {
+            Self::Finnhub(_) => Some(Provider::Finnhub),
+            Self::AlphaVantage(_) => Some(Provider::AlphaVantage),
+            Self::Watchlist(_) | Self::InvalidInput(_) | Self::Unsupported(_) => None,
+        }
+    }
+
+    /**
+     * Whether the provider itself failed (e.g. it is rate limited or down), so another
+     * provider may still serve the request. Errors about the requested data are final.
+     */
+    pub fn is_provider_unavailable(&self) -> bool {
+        match self {
+            Self::Finnhub(err) => !matches!(
+                err,
+                FinnhubError::Deserialization { .. } | FinnhubError::SymbolNotFound { .. }
+            ),
+            Self::AlphaVantage(err) => !matches!(
+                err,
+                AlphaVantageError::Deserialization { .. }
+                    | AlphaVantageError::SymbolNotFound { .. }
+                    | AlphaVantageError::CsvParsingFailed(_)
+                    | AlphaVantageError::RequestRejected { .. }
+            ),
+            Self::Unsupported(_) => true,
+            Self::Watchlist(_) | Self::InvalidInput(_) => false,
+        }
+    }
+
+    /**
+     * Machine readable error code, e.g. "rate_limited".
+     */
+    pub fn code(&self) -> &'static str {
+        self.status_and_code().1
+    }
+
+    fn status_and_code(&self) -> (StatusCode, &'static str) {
+        match self {
+            Self::Finnhub(err) => match err {
+                FinnhubError::HttpStatus { .. } => (StatusCode::BAD_GATEWAY, "upstream_error"),
+                FinnhubError::RateLimited { .. } => (StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+                FinnhubError::Deserialization { .. } => {
+                    (StatusCode::BAD_GATEWAY, "invalid_upstream_response")
+                }
+                FinnhubError::SymbolNotFound { .. } => (StatusCode::NOT_FOUND, "symbol_not_found"),
+                FinnhubError::InvalidApiKey => (StatusCode::BAD_GATEWAY, "invalid_api_key"),
+                FinnhubError::Timeout { .. } => (StatusCode::GATEWAY_TIMEOUT, "upstream_timeout"),
+                FinnhubError::RequestFailed { .. } => {
+                    (StatusCode::BAD_GATEWAY, "upstream_unavailable")
+                }
+            },
+            Self::AlphaVantage(err) => match err {
+                AlphaVantageError::HttpStatus { .. } => (StatusCode::BAD_GATEWAY, "upstream_error"),
+                AlphaVantageError::RateLimited { .. } => {
+                    (StatusCode::TOO_MANY_REQUESTS, "rate_limited")
+                }
+                AlphaVantageError::Deserialization { .. }
+                | AlphaVantageError::CsvParsingFailed(_) => {
+                    (StatusCode::BAD_GATEWAY, "invalid_upstream_response")
+                }
+                AlphaVantageError::SymbolNotFound { .. } => {
+                    (StatusCode::NOT_FOUND, "symbol_not_found")
+                }
+                AlphaVantageError::InvalidApiKey => (StatusCode::BAD_GATEWAY, "invalid_api_key"),
+                AlphaVantageError::RequestRejected { .. } => {
+                    (StatusCode::BAD_GATEWAY, "upstream_rejected_request")
+                }
+                AlphaVantageError::Timeout { .. } => {
+                    (StatusCode::GATEWAY_TIMEOUT, "upstream_timeout")
+                }
+                AlphaVantageError::RequestFailed { .. } => {
+                    (StatusCode::BAD_GATEWAY, "upstream_unavailable")
+                }
+            },
+            Self::Watchlist(err) => match err {
+                WatchlistError::NotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+                WatchlistError::InvalidName
+                | WatchlistError::InvalidSymbol(_)
+                | WatchlistError::TooManySymbols => (StatusCode::BAD_REQUEST, "invalid_input"),
+                WatchlistError::Persistence(_) => {
+                    (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+                }
+            },
+            Self::InvalidInput(_) => (StatusCode::BAD_REQUEST, "invalid_input"),
+            Self::Unsupported(_) => (StatusCode::NOT_IMPLEMENTED, "unsupported_operation"),
+        }
+    }
+}
+
+/**
+ * Shortens an upstream payload so it can be attached to an error message.
+ */
+pub fn payload_snippet(body: &str) -> String {
+    const MAX_CHARS: usize = 200;
+
+    if body.chars().count() <= MAX_CHARS {
+        return body.to_string();
+    }
+
+    let snippet: String = body.chars().take(MAX_CHARS).collect();
+    format!("{snippet}...")
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, code) = self.status_and_code();
+        if status.is_server_error() {
+            tracing::warn!(code, error = %logging::redact(&self.to_string()), "Request failed");
+        }
+        let body = Json(json!({
+            "code": code,
+            "message": self.to_string(),
+            "provider": self.provider(),
+        }));
+
+        (status, body).into_response()
+    }
+}
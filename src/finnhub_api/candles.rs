@@ -0,0 +1,60 @@
+use serde::Deserialize;
+
+use crate::candles::Candle;
+
+/**
+ * Gets returned from Finnhub, one array per field.
+ *
+ * s: status, "ok" or "no_data"
+ * t: UNIX timestamps
+ * o, h, l, c: open, high, low and close prices
+ * v: volumes
+ */
+#[derive(Deserialize, Debug)]
+pub struct CandlesResponse {
+    pub s: String,
+    #[serde(default)]
+    pub t: Vec<i64>,
+    #[serde(default)]
+    pub o: Vec<f64>,
+    #[serde(default)]
+    pub h: Vec<f64>,
+    #[serde(default)]
+    pub l: Vec<f64>,
+    #[serde(default)]
+    pub c: Vec<f64>,
+    #[serde(default)]
+    pub v: Vec<f64>,
+}
+
+impl CandlesResponse {
+    /**
+     * Zips the arrays into candles. Returns `None` if their lengths don't match.
+     */
+    pub fn into_candles(self) -> Option<Vec<Candle>> {
+        if self.s == "no_data" {
+            return Some(Vec::new());
+        }
+
+        let len = self.t.len();
+        if [&self.o, &self.h, &self.l, &self.c, &self.v]
+            .iter()
+            .any(|values| values.len() != len)
+        {
+            return None;
+        }
+
+        let candles = (0..len)
+            .map(|i| Candle {
+                timestamp: self.t[i],
+                open: self.o[i],
+                high: self.h[i],
+                low: self.l[i],
+                close: self.c[i],
+                volume: self.v[i],
+            })
+            .collect();
+
+        Some(candles)
+    }
+}
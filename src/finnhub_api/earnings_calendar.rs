@@ -0,0 +1,34 @@
+use serde::Deserialize;
+
+use crate::alphavantage_api::earnings_calendar::Earning;
+
+// https://finnhub.io/docs/api/earnings-calendar
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsCalendarResponse {
+    pub earnings_calendar: Vec<EarningsRelease>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsRelease {
+    symbol: String,
+    date: String,
+    eps_estimate: Option<f32>,
+}
+
+/**
+ * Releases in the shape of the Alpha Vantage earnings calendar.
+ */
+impl From<EarningsRelease> for Earning {
+    fn from(release: EarningsRelease) -> Self {
+        Earning {
+            symbol: release.symbol,
+            name: None,
+            report_date: release.date,
+            fiscal_date_ending: None,
+            estimate: release.eps_estimate,
+            currency: None,
+        }
+    }
+}
@@ -1,37 +1,147 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use super::candles::CandlesResponse;
 use super::company_profile::CompanyProfile;
+use super::earnings_calendar::{EarningsCalendarResponse, EarningsRelease};
 use super::market_news::ArticleMarketNews;
 use super::social_sentiment::SocialSentimentResponse;
-use super::symbol_quote::{SymbolQuote, SymbolQuoteExtended};
-use reqwest::Method;
-use serde::{Deserialize, Serialize};
+use super::symbol_quote::{MarketQuotes, SymbolQuote, SymbolQuoteExtended, SymbolQuoteFailure};
+use crate::candles::{Candle, CandleRange};
+use crate::error::{payload_snippet, Provider};
+use crate::health::{ErrorKind, ProviderHealth, ProviderHealthSnapshot};
+use crate::indices::Constituent;
+use crate::logging;
+use crate::rate_limit::TokenBucket;
+use crate::retry::{RetryPolicy, RetryStatsSnapshot, Retryable};
+use crate::single_flight::SingleFlight;
+use reqwest::{Method, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use url::Url;
 
 const BASE_URL: &str = "https://finnhub.io/api/v1/";
 
-#[derive(thiserror::Error, Debug)]
+// Sending the token as a header keeps it out of the URLs
+const API_KEY_HEADER: &str = "X-Finnhub-Token";
+
+// Free plan: 60 calls per minute, at most 30 per second
+pub const RATE_LIMIT_BURST: u32 = 30;
+pub const RATE_LIMIT_PER_MINUTE: u32 = 60;
+const RATE_LIMIT_MAX_WAIT: Duration = Duration::from_secs(30);
+
+#[derive(thiserror::Error, Debug, Clone)]
 pub enum FinnhubError {
-    #[error("Failed fetching the market news")]
-    AsyncRequestFailed(#[from] reqwest::Error),
+    #[error("Finnhub responded to the {endpoint} request with HTTP status {status}")]
+    HttpStatus { endpoint: &'static str, status: u16 },
+    #[error("The Finnhub rate limit is exhausted")]
+    RateLimited { retry_after: Option<u64> },
+    #[error("Failed to deserialize the Finnhub {endpoint} response ({message}): {snippet}")]
+    Deserialization {
+        endpoint: &'static str,
+        message: String,
+        snippet: String,
+    },
+    #[error("Finnhub has no quote for the symbol {symbol}")]
+    SymbolNotFound { symbol: String },
+    #[error("The Finnhub API key was rejected")]
+    InvalidApiKey,
+    #[error("The Finnhub {endpoint} request timed out")]
+    Timeout { endpoint: &'static str },
+    #[error("The Finnhub {endpoint} request failed: {message}")]
+    RequestFailed {
+        endpoint: &'static str,
+        message: String,
+    },
 }
 
-#[derive(Debug)]
+impl FinnhubError {
+    fn from_reqwest(endpoint: &'static str, err: reqwest::Error) -> FinnhubError {
+        if err.is_timeout() {
+            FinnhubError::Timeout { endpoint }
+        } else {
+            FinnhubError::RequestFailed {
+                endpoint,
+                // The URL isn't needed, the endpoint is already part of the message
+                message: err.without_url().to_string(),
+            }
+        }
+    }
+}
+
+impl ErrorKind for FinnhubError {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::HttpStatus { .. } => "http_status",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::Deserialization { .. } => "deserialization",
+            Self::SymbolNotFound { .. } => "symbol_not_found",
+            Self::InvalidApiKey => "invalid_api_key",
+            Self::Timeout { .. } => "timeout",
+            Self::RequestFailed { .. } => "request_failed",
+        }
+    }
+}
+
+impl Retryable for FinnhubError {
+    fn status(&self) -> Option<u16> {
+        match self {
+            Self::HttpStatus { status, .. } => Some(*status),
+            Self::RateLimited { .. } => Some(429),
+            _ => None,
+        }
+    }
+
+    fn is_transient(&self) -> bool {
+        matches!(self, Self::Timeout { .. } | Self::RequestFailed { .. })
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after } => retry_after.map(Duration::from_secs),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Endpoint {
     MarketNews,
     CompanyNews,
     Quote,
     CompanyProfile,
     SocialSentiment,
+    Candles,
+    EarningsCalendar,
 }
 
-impl ToString for Endpoint {
-    fn to_string(&self) -> String {
+impl Endpoint {
+    /**
+     * Path of the endpoint relative to the API URL.
+     */
+    pub fn path(&self) -> &'static str {
+        match self {
+            Self::MarketNews => "news",
+            Self::CompanyNews => "company-news",
+            Self::Quote => "quote",
+            Self::CompanyProfile => "stock/profile2",
+            Self::SocialSentiment => "stock/social-sentiment",
+            Self::Candles => "stock/candle",
+            Self::EarningsCalendar => "calendar/earnings",
+        }
+    }
+
+    /**
+     * Short name of the endpoint used in error messages.
+     */
+    pub fn name(&self) -> &'static str {
         match self {
-            Self::MarketNews => "news?category=general".to_string(),
-            Self::CompanyNews => "company-news".to_string(),
-            Self::Quote => "quote?symbol=".to_string(),
-            Self::CompanyProfile => "stock/profile2?symbol=".to_string(),
-            Self::SocialSentiment => "stock/social-sentiment".to_string(),
+            Self::MarketNews => "market news",
+            Self::CompanyNews => "company news",
+            Self::Quote => "quote",
+            Self::CompanyProfile => "company profile",
+            Self::SocialSentiment => "social sentiment",
+            Self::Candles => "candles",
+            Self::EarningsCalendar => "earnings calendar",
         }
     }
 }
@@ -42,230 +152,431 @@ pub struct RateLimitInfo {
     pub ratelimit_reset: String,
 }
 
-#[derive(Debug)]
+/**
+ * Body and rate limit headers of a successful Finnhub response.
+ */
+#[derive(Debug, Clone)]
+struct FinnhubResponse {
+    body: String,
+    rate_limit_info: Option<RateLimitInfo>,
+}
+
+/**
+ * Cheap to clone. Clones share the HTTP connection pool, the in-flight requests and the rate
+ * limiter, so concurrent identical requests from all clones only hit Finnhub once and all of
+ * them are paced together.
+ */
+#[derive(Debug, Clone)]
 pub struct FinnhubAPI {
+    base_url: Url,
     api_key: String,
     endpoint: Endpoint,
+    client: reqwest::Client,
+    in_flight: SingleFlight<Result<FinnhubResponse, FinnhubError>>,
+    rate_limiter: Arc<TokenBucket>,
+    retry_policy: RetryPolicy,
+    health: Arc<ProviderHealth>,
+    /**
+     * Rate limit headers of the latest response
+     */
+    rate_limit_info: Arc<Mutex<Option<RateLimitInfo>>>,
 }
 
 impl FinnhubAPI {
-    pub fn new(api_key: &str) -> FinnhubAPI {
+    pub fn new(api_key: &str, client: reqwest::Client) -> FinnhubAPI {
         FinnhubAPI {
+            base_url: Url::parse(BASE_URL).expect("the Finnhub API URL to be valid"),
             api_key: api_key.to_string(),
             endpoint: Endpoint::MarketNews,
+            client,
+            in_flight: SingleFlight::new(),
+            rate_limiter: Arc::new(TokenBucket::new(
+                RATE_LIMIT_BURST,
+                RATE_LIMIT_PER_MINUTE,
+                RATE_LIMIT_MAX_WAIT,
+            )),
+            retry_policy: RetryPolicy::default(),
+            health: Arc::new(ProviderHealth::default()),
+            rate_limit_info: Arc::new(Mutex::new(None)),
         }
     }
 
+    /**
+     * Overrides the Finnhub API URL, e.g. to point the client to a mock server.
+     */
+    pub fn base_url(&mut self, mut base_url: Url) -> &mut FinnhubAPI {
+        // The endpoint paths are appended to the last path segment only with a trailing slash
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+        self.base_url = base_url;
+        self
+    }
+
     pub fn endpoint(&mut self, endpoint: Endpoint) -> &mut FinnhubAPI {
         self.endpoint = endpoint;
         self
     }
 
-    fn get_api_token(&self) -> String {
-        format!("&token={}", self.api_key)
+    pub fn rate_limiter(&mut self, rate_limiter: TokenBucket) -> &mut FinnhubAPI {
+        self.rate_limiter = Arc::new(rate_limiter);
+        self
+    }
+
+    pub fn retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut FinnhubAPI {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn retry_stats(&self) -> RetryStatsSnapshot {
+        self.retry_policy.stats()
+    }
+
+    pub fn health(&self) -> ProviderHealthSnapshot {
+        self.health.snapshot()
     }
 
     /**
-     *  Possibilities for url_add
-     * 1) Endpoint::Quote: a stock symbol like "AAPL" or "IBM"
-     * 2) Endpoint::CompanyProfile: a stock symbol like "AAPL" or "IBM"
+     * Rate limit headers of the latest Finnhub response, `None` before the first one.
      */
-    fn prepare_url(&self, url_add: Option<&str>) -> String {
-        if let Some(url) = url_add {
-            format!(
-                "{}{}{}{}",
-                BASE_URL,
-                self.endpoint.to_string(),
-                url,
-                self.get_api_token(),
-            )
-        } else {
-            format!(
-                "{}{}{}",
-                BASE_URL,
-                self.endpoint.to_string(),
-                self.get_api_token(),
-            )
+    pub fn rate_limit_info(&self) -> Option<RateLimitInfo> {
+        self.rate_limit_info.lock().unwrap().clone()
+    }
+
+    /**
+     * Checks that Finnhub is reachable. Any response counts, the request has no token and
+     * doesn't count against the rate limit.
+     */
+    pub async fn ping(&self) -> Result<(), FinnhubError> {
+        self.client
+            .request(Method::GET, self.base_url.clone())
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| FinnhubError::from_reqwest("ping", err))
+    }
+
+    /**
+     * Average time between two requests allowed by the rate limit.
+     */
+    pub fn request_interval(&self) -> Duration {
+        self.rate_limiter.refill_interval()
+    }
+
+    /**
+     * URL of the endpoint with the encoded query parameters. The token isn't part of it, it is
+     * sent as a header.
+     */
+    fn prepare_url(&self, query: &[(&str, &str)]) -> Url {
+        let mut url = self
+            .base_url
+            .join(self.endpoint.path())
+            .expect("the endpoint paths to be valid");
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
         }
+        url
     }
 
-    pub async fn fetch_market_news(&self) -> Result<Vec<ArticleMarketNews>, FinnhubError> {
-        let url = self.prepare_url(None);
-        let client = reqwest::Client::new();
-        let req = client
+    /**
+     * Sends a GET request to the given url and reads the response. Failed requests are
+     * retried according to the retry policy. Concurrent requests for the same url share a
+     * single upstream request.
+     */
+    async fn request(&self, url: Url) -> Result<FinnhubResponse, FinnhubError> {
+        let result = self
+            .in_flight
+            .run(url.as_str(), || {
+                self.retry_policy.run(|| {
+                    logging::upstream_call(
+                        Provider::Finnhub,
+                        self.endpoint.name(),
+                        url.as_str(),
+                        self.send(url.clone()),
+                    )
+                })
+            })
+            .await;
+        self.health.record(&result);
+
+        result
+    }
+
+    async fn send(&self, url: Url) -> Result<FinnhubResponse, FinnhubError> {
+        let endpoint = self.endpoint.name();
+
+        if let Err(wait) = self.rate_limiter.acquire().await {
+            return Err(FinnhubError::RateLimited {
+                retry_after: Some(wait.as_secs().max(1)),
+            });
+        }
+
+        let response = self
+            .client
             .request(Method::GET, url)
-            .build()
-            .map_err(FinnhubError::AsyncRequestFailed)?;
+            .header(API_KEY_HEADER, &self.api_key)
+            .send()
+            .await
+            .map_err(|err| FinnhubError::from_reqwest(endpoint, err))?;
+        logging::record_status(response.status());
+
+        let rate_limit_info = rate_limit_info(response.headers());
+        if let Some(info) = &rate_limit_info {
+            if let (Ok(remaining), Ok(reset)) = (
+                info.ratelimit_remaining.parse::<u32>(),
+                info.ratelimit_reset.parse::<u64>(),
+            ) {
+                self.rate_limiter.update(remaining, reset);
+            }
+            *self.rate_limit_info.lock().unwrap() = Some(info.clone());
+        }
 
-        let res = client
-            .execute(req)
-            .await?
-            .json()
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::UNAUTHORIZED => return Err(FinnhubError::InvalidApiKey),
+            StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after = retry_after_secs(response.headers());
+                self.rate_limiter
+                    .block_for(Duration::from_secs(retry_after.unwrap_or(60)));
+                return Err(FinnhubError::RateLimited { retry_after });
+            }
+            status => {
+                return Err(FinnhubError::HttpStatus {
+                    endpoint,
+                    status: status.as_u16(),
+                })
+            }
+        }
+
+        let body = response
+            .text()
             .await
-            .map_err(FinnhubError::AsyncRequestFailed)?;
-        Ok(res)
+            .map_err(|err| FinnhubError::from_reqwest(endpoint, err))?;
+
+        Ok(FinnhubResponse {
+            body,
+            rate_limit_info,
+        })
     }
 
-    pub async fn fetch_company_news(&self, symbol: &str, time_from: &str, time_to: &str) -> Result<Vec<ArticleMarketNews>, FinnhubError> {
-        let url_add = format!("?symbol={symbol}&from={time_from}&to={time_to}");
-        let url = self.prepare_url(Some(&url_add));
-        let client = reqwest::Client::new();
-        let req = client
-            .request(Method::GET, url)
-            .build()
-            .map_err(FinnhubError::AsyncRequestFailed)?;
+    /**
+     * Deserializes the response body into `T`.
+     */
+    fn parse_json<T: DeserializeOwned>(
+        &self,
+        response: &FinnhubResponse,
+    ) -> Result<T, FinnhubError> {
+        serde_json::from_str(&response.body).map_err(|err| {
+            self.response_error(FinnhubError::Deserialization {
+                endpoint: self.endpoint.name(),
+                message: err.to_string(),
+                snippet: payload_snippet(&response.body),
+            })
+        })
+    }
 
-        let res = client
-            .execute(req)
-            .await?
-            .json()
-            .await
-            .map_err(FinnhubError::AsyncRequestFailed)?;
-        Ok(res)
+    /**
+     * Records an error about a response body, e.g. invalid JSON, which the request itself
+     * didn't fail with.
+     */
+    fn response_error(&self, err: FinnhubError) -> FinnhubError {
+        self.health.record_error(&err);
+        err
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: Url) -> Result<T, FinnhubError> {
+        let response = self.request(url).await?;
+        self.parse_json(&response)
+    }
+
+    pub async fn fetch_market_news(&self) -> Result<Vec<ArticleMarketNews>, FinnhubError> {
+        let url = self.prepare_url(&[("category", "general")]);
+        self.get_json(url).await
+    }
+
+    pub async fn fetch_company_news(
+        &self,
+        symbol: &str,
+        time_from: &str,
+        time_to: &str,
+    ) -> Result<Vec<ArticleMarketNews>, FinnhubError> {
+        let url = self.prepare_url(&[("symbol", symbol), ("from", time_from), ("to", time_to)]);
+        self.get_json(url).await
     }
 
-    pub async fn fetch_quotes_for_market(
+    /**
+     * Fetches the quote of a single symbol. `name` is only passed through to the quote.
+     */
+    pub async fn fetch_quote(
         &self,
-        market: &'static [(&str, &str)],
-    ) -> Result<Vec<SymbolQuoteExtended>, FinnhubError> {
-        let client = reqwest::Client::new();
+        symbol: &str,
+        name: &str,
+    ) -> Result<SymbolQuoteExtended, FinnhubError> {
+        let url = self.prepare_url(&[("symbol", symbol)]);
+        let response = self.request(url).await?;
+        let quote: SymbolQuote = self.parse_json(&response)?;
+
+        // Finnhub answers unknown symbols with a quote of zeros and no changes. Not an error of
+        // the provider, so it isn't recorded in its health.
+        let (delta, delta_percent) = match (quote.d, quote.dp) {
+            (Some(delta), Some(delta_percent)) if quote.t != 0 => (delta, delta_percent),
+            _ => {
+                return Err(FinnhubError::SymbolNotFound {
+                    symbol: symbol.to_string(),
+                })
+            }
+        };
+
+        Ok(SymbolQuoteExtended {
+            current_price: quote.c,
+            delta,
+            delta_percent,
+            high: quote.h,
+            low: quote.l,
+            open: quote.o,
+            previous_close: quote.pc,
+            timestamp: quote.t,
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            provider: Provider::Finnhub,
+            rate_limit_info: response.rate_limit_info,
+        })
+    }
 
+    /**
+     * Fetches the quote of every symbol in the market. Symbols whose quote could not be
+     * fetched end up in `MarketQuotes::failures` instead of the quotes.
+     */
+    pub async fn fetch_quotes_for_market(&self, market: &[Constituent]) -> MarketQuotes {
         let mut tasks = Vec::new();
 
-        // Iterate over every symbol in the Dow Jones (30)
-        for (symbol, name) in market.iter() {
-            let client = client.clone();
-            let url = self.prepare_url(Some(symbol));
+        // Iterate over every symbol of the market, e.g. the Dow Jones (30)
+        for constituent in market.iter() {
+            let fh_api = self.clone();
+            let Constituent { symbol, name } = constituent.clone();
 
-            // Span a seperate task for each request with the cloned reqwest client and return
+            // Span a seperate task for each request with the cloned API client and return
             // the expected data from the request
-            let task = tokio::task::spawn(async move {
-                let req = client
-                    .request(Method::GET, url)
-                    .build()
-                    .expect("the request to be build.");
-
-                let response = client
-                    .execute(req)
-                    .await
-                    .expect("the request to be executed");
-                let headers = response.headers().clone();
-                let ratelimit_remaining = headers.get("X-Ratelimit-Remaining").unwrap();
-                let ratelimit_reset = headers.get("X-Ratelimit-Reset").unwrap();
-                let rate_limit_info = RateLimitInfo {
-                    ratelimit_remaining: String::from(ratelimit_remaining.to_str().unwrap()),
-                    ratelimit_reset: String::from(ratelimit_reset.to_str().unwrap()),
-                };
-
-                let quote = response.json().await.unwrap_or(SymbolQuote {
-                    c: 0.0,
-                    d: 0.0,
-                    dp: 0.0,
-                    h: 0.0,
-                    l: 0.0,
-                    o: 0.0,
-                    pc: 0.0,
-                    t: SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis(),
-                });
-
-                SymbolQuoteExtended {
-                    current_price: quote.c,
-                    delta: quote.d,
-                    delta_percent: quote.dp,
-                    high: quote.h,
-                    low: quote.l,
-                    open: quote.o,
-                    previous_close: quote.pc,
-                    timestamp: quote.t,
-                    symbol: symbol.to_string(),
-                    name: name.to_string(),
-                    rate_limit_info,
-                }
-            });
+            let task = tokio::task::spawn(async move { fh_api.fetch_quote(&symbol, &name).await });
 
-            tasks.push(task);
+            tasks.push((constituent, task));
         }
 
-        let mut quotes_fe: Vec<SymbolQuoteExtended> = vec![];
+        let mut market_quotes = MarketQuotes::default();
 
         // Loop through each task and await the result from the Future
-        for task in tasks {
-            let quote_fe = match task.await {
-                Ok(q) => q,
-                Err(_) => SymbolQuoteExtended {
-                    current_price: 0.0,
-                    delta: 0.0,
-                    delta_percent: 0.0,
-                    high: 0.0,
-                    low: 0.0,
-                    open: 0.0,
-                    previous_close: 0.0,
-                    timestamp: SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis(),
-                    symbol: "".to_string(),
-                    name: "".to_string(),
-                    rate_limit_info: RateLimitInfo {
-                        ratelimit_remaining: "0".to_string(),
-                        ratelimit_reset: "0".to_string(),
-                    },
+        for (constituent, task) in tasks {
+            let error = match task.await {
+                Ok(Ok(quote)) => {
+                    market_quotes.quotes.push(quote);
+                    continue;
+                }
+                Ok(Err(err)) => err,
+                Err(join_error) => FinnhubError::RequestFailed {
+                    endpoint: self.endpoint.name(),
+                    message: join_error.to_string(),
                 },
             };
 
-            quotes_fe.push(quote_fe);
+            market_quotes.failures.push(SymbolQuoteFailure {
+                symbol: constituent.symbol.clone(),
+                name: constituent.name.clone(),
+                error: error.into(),
+            });
         }
 
-        Ok(quotes_fe)
+        market_quotes
     }
 
     pub async fn fetch_company_profile(
         &self,
         symbol: &str,
     ) -> Result<CompanyProfile, FinnhubError> {
-        let client = reqwest::Client::new();
-        let url = self.prepare_url(Some(symbol));
-
-        let req = client
-            .request(Method::GET, url)
-            .build()
-            .expect("the request to be build.");
-
-        let result = client
-            .execute(req)
-            .await
-            .expect("the request to be executed")
-            .json::<CompanyProfile>()
-            .await
-            .expect("the company profile response to be parsed to JSON");
-
-        Ok(result)
+        let url = self.prepare_url(&[("symbol", symbol)]);
+        self.get_json(url).await
     }
 
+    /**
+     * Social media sentiment of a symbol since a yyyy-mm-dd date.
+     */
     pub async fn fetch_social_sentiment(
         &self,
-        // ?symbol=XXX&?from=yyyy-mm-dd
-        url_add: &str,
+        symbol: &str,
+        time_from: &str,
     ) -> Result<SocialSentimentResponse, FinnhubError> {
-        let client = reqwest::Client::new();
-        let url = self.prepare_url(Some(url_add));
+        let url = self.prepare_url(&[("symbol", symbol), ("from", time_from)]);
+        self.get_json(url).await
+    }
 
-        let req = client
-            .request(Method::GET, url)
-            .build()
-            .expect("the request to be build.");
+    /**
+     * Earnings releases between two yyyy-mm-dd dates (inclusive).
+     */
+    pub async fn fetch_earnings_calendar(
+        &self,
+        time_from: &str,
+        time_to: &str,
+    ) -> Result<Vec<EarningsRelease>, FinnhubError> {
+        let url = self.prepare_url(&[("from", time_from), ("to", time_to)]);
+        let res: EarningsCalendarResponse = self.get_json(url).await?;
 
-        let result = client
-            .execute(req)
-            .await
-            .expect("the request to be executed")
-            .json::<SocialSentimentResponse>()
-            .await
-            .expect("the social sentiment response to be parsed to JSON");
+        Ok(res.earnings_calendar)
+    }
 
-        Ok(result)
+    pub async fn fetch_candles(
+        &self,
+        symbol: &str,
+        range: &CandleRange,
+    ) -> Result<Vec<Candle>, FinnhubError> {
+        let url = self.prepare_url(&[
+            ("symbol", symbol),
+            ("resolution", range.resolution.as_str()),
+            ("from", &range.from.to_string()),
+            ("to", &range.to.to_string()),
+        ]);
+        let response = self.request(url).await?;
+        let candles: CandlesResponse = self.parse_json(&response)?;
+
+        candles.into_candles().ok_or_else(|| {
+            self.response_error(FinnhubError::Deserialization {
+                endpoint: self.endpoint.name(),
+                message: "the candle arrays differ in length".to_string(),
+                snippet: payload_snippet(&response.body),
+            })
+        })
+    }
+}
+
+fn rate_limit_info(headers: &reqwest::header::HeaderMap) -> Option<RateLimitInfo> {
+    let header_value = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+
+    Some(RateLimitInfo {
+        ratelimit_remaining: header_value("X-Ratelimit-Remaining")?,
+        ratelimit_reset: header_value("X-Ratelimit-Reset")?,
+    })
+}
+
+/**
+ * Seconds until the rate limit resets, taken from `Retry-After` or `X-Ratelimit-Reset`.
+ */
+fn retry_after_secs(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    let header_value = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+
+    if let Some(retry_after) = header_value("Retry-After") {
+        return Some(retry_after);
     }
+
+    let reset = header_value("X-Ratelimit-Reset")?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(reset.saturating_sub(now))
 }
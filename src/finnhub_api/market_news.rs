@@ -2,14 +2,14 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug, Serialize)]
 pub struct ArticleMarketNews {
-    headline: String,
-    category: String,
-    datetime: u32,
-    id: u32,
-    image: String,
-    source: String,
-    summary: String,
-    url: String,
+    pub headline: String,
+    pub category: String,
+    pub datetime: u32,
+    pub id: u32,
+    pub image: String,
+    pub source: String,
+    pub summary: String,
+    pub url: String,
 }
 
 #[derive(Deserialize)]
@@ -1,5 +1,8 @@
+pub mod candles;
+pub mod company_profile;
+pub mod earnings_calendar;
 pub mod lib;
 pub mod market_news;
-pub mod symbol_quote;
-pub mod company_profile;
+pub mod provider;
 pub mod social_sentiment;
+pub mod symbol_quote;
@@ -0,0 +1,126 @@
+use async_trait::async_trait;
+
+use super::{
+    company_profile::CompanyProfile,
+    lib::{Endpoint, FinnhubAPI},
+    market_news::ArticleMarketNews,
+    social_sentiment::SocialSentimentResponse,
+    symbol_quote::{MarketQuotes, SymbolQuoteExtended},
+};
+use crate::{
+    alphavantage_api::earnings_calendar::Earning,
+    candles::{format_date, unix_now, Candle, CandleRange},
+    error::{ApiError, Provider},
+    indices::Constituent,
+    provider::{MarketDataProvider, Operation},
+};
+
+/**
+ * Same horizon as the Alpha Vantage earnings calendar.
+ */
+const EARNINGS_CALENDAR_DAYS: i64 = 90;
+
+impl FinnhubAPI {
+    /**
+     * A copy of the client which requests the given endpoint.
+     */
+    fn with_endpoint(&self, endpoint: Endpoint) -> FinnhubAPI {
+        let mut finnhub_api = self.clone();
+        finnhub_api.endpoint(endpoint);
+        finnhub_api
+    }
+}
+
+#[async_trait]
+impl MarketDataProvider for FinnhubAPI {
+    fn provider(&self) -> Provider {
+        Provider::Finnhub
+    }
+
+    fn operations(&self) -> &'static [Operation] {
+        &[
+            Operation::Quote,
+            Operation::MarketNews,
+            Operation::CompanyNews,
+            Operation::CompanyProfile,
+            Operation::SocialSentiment,
+            Operation::EarningsCalendar,
+            Operation::Candles,
+        ]
+    }
+
+    async fn quote(&self, symbol: &str, name: &str) -> Result<SymbolQuoteExtended, ApiError> {
+        let quote = self
+            .with_endpoint(Endpoint::Quote)
+            .fetch_quote(symbol, name)
+            .await?;
+        Ok(quote)
+    }
+
+    async fn quotes(&self, market: &[Constituent]) -> MarketQuotes {
+        self.with_endpoint(Endpoint::Quote)
+            .fetch_quotes_for_market(market)
+            .await
+    }
+
+    async fn market_news(&self) -> Result<Vec<ArticleMarketNews>, ApiError> {
+        let articles = self
+            .with_endpoint(Endpoint::MarketNews)
+            .fetch_market_news()
+            .await?;
+        Ok(articles)
+    }
+
+    async fn company_news(
+        &self,
+        symbol: &str,
+        time_from: &str,
+        time_to: &str,
+    ) -> Result<Vec<ArticleMarketNews>, ApiError> {
+        let articles = self
+            .with_endpoint(Endpoint::CompanyNews)
+            .fetch_company_news(symbol, time_from, time_to)
+            .await?;
+        Ok(articles)
+    }
+
+    async fn company_profile(&self, symbol: &str) -> Result<CompanyProfile, ApiError> {
+        let profile = self
+            .with_endpoint(Endpoint::CompanyProfile)
+            .fetch_company_profile(symbol)
+            .await?;
+        Ok(profile)
+    }
+
+    async fn social_sentiment(
+        &self,
+        symbol: &str,
+        time_from: &str,
+    ) -> Result<SocialSentimentResponse, ApiError> {
+        let social_sentiment = self
+            .with_endpoint(Endpoint::SocialSentiment)
+            .fetch_social_sentiment(symbol, time_from)
+            .await?;
+        Ok(social_sentiment)
+    }
+
+    async fn earnings_calendar(&self) -> Result<Vec<Earning>, ApiError> {
+        let now = unix_now();
+        let releases = self
+            .with_endpoint(Endpoint::EarningsCalendar)
+            .fetch_earnings_calendar(
+                &format_date(now),
+                &format_date(now + EARNINGS_CALENDAR_DAYS * 24 * 60 * 60),
+            )
+            .await?;
+        Ok(releases.into_iter().map(Earning::from).collect())
+    }
+
+    async fn candles(&self, symbol: &str, range: &CandleRange) -> Result<Vec<Candle>, ApiError> {
+        let candles = self
+            .with_endpoint(Endpoint::Candles)
+            .fetch_candles(symbol, range)
+            .await?;
+        Ok(candles)
+    }
+}
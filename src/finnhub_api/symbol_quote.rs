@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
-use super::lib::RateLimitInfo;
+use super::{company_profile::CompanyProfile, lib::RateLimitInfo};
+use crate::error::{ApiError, Provider};
 
 /**
  * Gets returned from Finnhub.
@@ -12,12 +13,14 @@ use super::lib::RateLimitInfo;
  * l: low price of the day
  * o: open price of the day
  * pc: previous close price
+ *
+ * The changes are `null` for unknown symbols.
  */
 #[derive(Deserialize, Debug, Serialize)]
 pub struct SymbolQuote {
     pub c: f32,
-    pub d: f32,
-    pub dp: f32,
+    pub d: Option<f32>,
+    pub dp: Option<f32>,
     pub h: f32,
     pub l: f32,
     pub o: f32,
@@ -40,7 +43,27 @@ pub struct SymbolQuoteExtended {
     pub timestamp: u128,
     pub symbol: String,
     pub name: String,
-    pub rate_limit_info: RateLimitInfo,
+    pub provider: Provider,
+    pub rate_limit_info: Option<RateLimitInfo>,
+}
+
+/*
+ * A symbol whose quote could not be fetched.
+ */
+#[derive(Debug)]
+pub struct SymbolQuoteFailure {
+    pub symbol: String,
+    pub name: String,
+    pub error: ApiError,
+}
+
+/*
+ * Quotes for all symbols of a market, split into the fetched quotes and the failed symbols.
+ */
+#[derive(Debug, Default)]
+pub struct MarketQuotes {
+    pub quotes: Vec<SymbolQuoteExtended>,
+    pub failures: Vec<SymbolQuoteFailure>,
 }
 
 /*
@@ -58,4 +81,53 @@ pub struct SymbolQuoteFrontend {
     pub timestamp: u128,
     pub symbol: String,
     pub name: String,
+    /**
+     * The provider which served the quote
+     */
+    pub provider: Provider,
+}
+
+impl From<SymbolQuoteExtended> for SymbolQuoteFrontend {
+    fn from(quote: SymbolQuoteExtended) -> Self {
+        SymbolQuoteFrontend {
+            current_price: quote.current_price,
+            delta: quote.delta,
+            delta_percent: quote.delta_percent,
+            high: quote.high,
+            low: quote.low,
+            open: quote.open,
+            previous_close: quote.previous_close,
+            timestamp: quote.timestamp,
+            symbol: quote.symbol,
+            name: quote.name,
+            provider: quote.provider,
+        }
+    }
+}
+
+/*
+ * Comma separated symbols, e.g. ?symbols=AAPL,TSLA,SAP
+ */
+#[derive(Deserialize)]
+pub struct QuerySymbols {
+    pub symbols: String,
+}
+
+/*
+ * Quote of a single symbol, optionally with the profile of the company.
+ */
+#[derive(Serialize, Debug)]
+pub struct SymbolQuoteWithProfile {
+    #[serde(flatten)]
+    pub quote: SymbolQuoteFrontend,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<CompanyProfile>,
+}
+
+/*
+ * Additional data for a single quote, e.g. ?include=profile
+ */
+#[derive(Deserialize)]
+pub struct QueryQuoteInclude {
+    pub include: Option<String>,
 }
@@ -1,5 +1,4 @@
-use csv::ReaderBuilder;
-use std::sync::Arc;
+use std::{cmp::Ordering, sync::Arc};
 
 use axum::{
     extract::{Query, State},
@@ -11,40 +10,35 @@ use serde_json::{json, Value};
 use crate::{
     alphavantage_api::{
         earnings_calendar::Earning,
-        lib::{AlphaVantageAPI, Endpoint},
         market_status::MarketStatusInfo,
         news_sentiment::{NewsSentimentFeedEntry, QueryNewsSentiment, QueryNewsSentimentTicker},
     },
+    error::ApiError,
+    provider::{Operation, ServedBy},
     AppState,
 };
 
-fn setup_av_api(endpoint: Endpoint, api_token: &str) -> AlphaVantageAPI {
-    let mut av_api = AlphaVantageAPI::new(api_token);
-    av_api.endpoint(endpoint);
-    av_api
-}
-
 pub async fn get_market_status(
     State(state): State<Arc<AppState>>,
-) -> (StatusCode, Json<Vec<MarketStatusInfo>>) {
-    let av_api = setup_av_api(Endpoint::MarketStatus, &state.api_token_alphavantage);
-    let markets_status = av_api
-        .fetch_market_status()
-        .await
-        .expect("The market status to be fetched");
-    (StatusCode::OK, Json(markets_status))
+) -> Result<(StatusCode, ServedBy, Json<Vec<MarketStatusInfo>>), ApiError> {
+    let (provider, markets_status) = state
+        .market_data
+        .serve(Operation::MarketStatus, |provider| provider.market_status())
+        .await?;
+    Ok((StatusCode::OK, provider.into(), Json(markets_status)))
 }
 
 pub async fn get_news_sentiment(
     State(state): State<Arc<AppState>>,
     time_from: Query<QueryNewsSentiment>,
-) -> (StatusCode, Json<Value>) {
+) -> Result<(StatusCode, ServedBy, Json<Value>), ApiError> {
     let time_from: QueryNewsSentiment = time_from.0;
-    let av_api = setup_av_api(Endpoint::NewsSentiment, &state.api_token_alphavantage);
-    let news_sentiment = av_api
-        .fetch_news_sentiment(time_from.time_from)
-        .await
-        .expect("The news sentiment to be fetched");
+    let (provider, news_sentiment) = state
+        .market_data
+        .serve(Operation::NewsSentiment, |provider| {
+            provider.news_sentiment(None, &time_from.time_from)
+        })
+        .await?;
 
     let mut bullish: Vec<&NewsSentimentFeedEntry> = news_sentiment
         .iter()
@@ -53,8 +47,7 @@ pub async fn get_news_sentiment(
 
     bullish.sort_by(|a, b| {
         b.overall_sentiment_score
-            .partial_cmp(&a.overall_sentiment_score)
-            .unwrap()
+            .total_cmp(&a.overall_sentiment_score)
     });
 
     let mut bearish: Vec<&NewsSentimentFeedEntry> = news_sentiment
@@ -64,30 +57,31 @@ pub async fn get_news_sentiment(
 
     bearish.sort_by(|a, b| {
         a.overall_sentiment_score
-            .partial_cmp(&b.overall_sentiment_score)
-            .unwrap()
+            .total_cmp(&b.overall_sentiment_score)
     });
 
-    (
+    Ok((
         StatusCode::OK,
+        provider.into(),
         Json(json!({
             "news_bullish": bullish,
             "news_bearish": bearish,
         })),
-    )
+    ))
 }
 
 pub async fn get_news_sentiment_ticker(
     State(state): State<Arc<AppState>>,
     query: Query<QueryNewsSentimentTicker>,
-) -> (StatusCode, Json<Value>) {
+) -> Result<(StatusCode, ServedBy, Json<Value>), ApiError> {
     let time_from = query.0.time_from;
     let ticker = query.0.ticker;
-    let av_api = setup_av_api(Endpoint::NewsSentiment, &state.api_token_alphavantage);
-    let news_sentiment = av_api
-        .fetch_news_sentiment_ticker(ticker, time_from)
-        .await
-        .expect("The news sentiment for a ticker to be fetched");
+    let (provider, news_sentiment) = state
+        .market_data
+        .serve(Operation::NewsSentiment, |provider| {
+            provider.news_sentiment(Some(&ticker), &time_from)
+        })
+        .await?;
 
     let mut bullish: Vec<&NewsSentimentFeedEntry> = news_sentiment
         .iter()
@@ -96,8 +90,7 @@ pub async fn get_news_sentiment_ticker(
 
     bullish.sort_by(|a, b| {
         b.overall_sentiment_score
-            .partial_cmp(&a.overall_sentiment_score)
-            .unwrap()
+            .total_cmp(&a.overall_sentiment_score)
     });
 
     let mut bearish: Vec<&NewsSentimentFeedEntry> = news_sentiment
@@ -107,38 +100,33 @@ pub async fn get_news_sentiment_ticker(
 
     bearish.sort_by(|a, b| {
         a.overall_sentiment_score
-            .partial_cmp(&b.overall_sentiment_score)
-            .unwrap()
+            .total_cmp(&b.overall_sentiment_score)
     });
 
-    (
+    Ok((
         StatusCode::OK,
+        provider.into(),
         Json(json!({
             "news_bullish": bullish,
             "news_bearish": bearish,
         })),
-    )
+    ))
 }
 
 pub async fn get_earnings_calendar(
     State(state): State<Arc<AppState>>,
-) -> (StatusCode, Json<Value>) {
-    let av_api = setup_av_api(Endpoint::EarningsCalendar, &state.api_token_alphavantage);
-    let csv_string = av_api
-        .fetch_earnings_calendar()
-        .await
-        .expect("The earnings calendar to be fetched.");
-
-    let mut rdr = ReaderBuilder::new()
-        .delimiter(b',')
-        .from_reader(csv_string.as_bytes());
+) -> Result<(StatusCode, ServedBy, Json<Value>), ApiError> {
+    let (provider, earnings) = state
+        .market_data
+        .serve(Operation::EarningsCalendar, |provider| {
+            provider.earnings_calendar()
+        })
+        .await?;
 
     let mut estimates_high = Vec::<Earning>::new();
     let mut estimates_low = Vec::<Earning>::new();
 
-    for result in rdr.deserialize() {
-        let record: Earning = result.expect("The record to be an Earning");
-
+    for record in earnings {
         if let Some(estimate) = record.estimate {
             if estimate >= 1.5 {
                 estimates_high.push(record);
@@ -148,14 +136,25 @@ pub async fn get_earnings_calendar(
         }
     }
 
-    estimates_high.sort_by(|a, b| b.estimate.partial_cmp(&a.estimate).unwrap());
-    estimates_low.sort_by(|a, b| a.estimate.partial_cmp(&b.estimate).unwrap());
+    estimates_high.sort_by(|a, b| compare_estimates(b, a));
+    estimates_low.sort_by(compare_estimates);
 
-    (
+    Ok((
         StatusCode::OK,
+        provider.into(),
         Json(json!({
             "estimates_high": estimates_high,
             "estimates_low": estimates_low,
         })),
-    )
+    ))
+}
+
+/**
+ * Orders earnings by their estimate, earnings without one come first.
+ */
+fn compare_estimates(a: &Earning, b: &Earning) -> Ordering {
+    match (a.estimate, b.estimate) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
 }
@@ -0,0 +1,50 @@
+use crate::candles::{Candle, CandleRange, CandleSeries, QueryCandles};
+use crate::error::{ApiError, Provider};
+use crate::indices::normalize_symbol;
+use crate::provider::{Operation, ServedBy};
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::{http::StatusCode, Json};
+use std::sync::Arc;
+
+pub async fn get_candles(
+    Path(symbol): Path<String>,
+    State(state): State<Arc<AppState>>,
+    query: Query<QueryCandles>,
+) -> Result<(StatusCode, ServedBy, Json<CandleSeries>), ApiError> {
+    let symbol = normalize_symbol(&symbol)
+        .ok_or_else(|| ApiError::InvalidInput(format!("Invalid symbol '{symbol}'.")))?;
+    let range = CandleRange::from_query(&query.0)?;
+
+    let (provider, candles) = fetch_candles(&state, &symbol, &range).await?;
+
+    Ok((
+        StatusCode::OK,
+        provider.into(),
+        Json(CandleSeries {
+            symbol,
+            resolution: range.resolution,
+            from: range.from,
+            to: range.to,
+            provider,
+            candles,
+        }),
+    ))
+}
+
+/**
+ * Candles from the preferred provider. Finnhub only serves candles on paid plans, so
+ * Alpha Vantage is the usual fallback.
+ */
+pub async fn fetch_candles(
+    state: &AppState,
+    symbol: &str,
+    range: &CandleRange,
+) -> Result<(Provider, Vec<Candle>), ApiError> {
+    state
+        .market_data
+        .serve(Operation::Candles, |provider| {
+            provider.candles(symbol, range)
+        })
+        .await
+}
@@ -1,170 +1,211 @@
+use crate::error::ApiError;
 use crate::finnhub_api::company_profile::CompanyProfile;
-use crate::finnhub_api::lib::{Endpoint, FinnhubAPI};
 use crate::finnhub_api::market_news::{ArticleMarketNews, QueryCompanyNews};
 use crate::finnhub_api::social_sentiment::{QuerySocialSentiment, SocialSentimentResponse};
-use crate::finnhub_api::symbol_quote::SymbolQuoteFrontend;
-use crate::indices::{DOW_JONES, NASDAQ};
+use crate::finnhub_api::symbol_quote::{QueryQuoteInclude, QuerySymbols, SymbolQuoteWithProfile};
+use crate::indices::{normalize_symbol, Constituent, IndexDefinition};
+use crate::provider::{Operation, ServedBy};
+use crate::quotes::{summarize_quotes, QuoteBatch, QuoteSummary};
 use crate::AppState;
 use axum::extract::{Path, Query, State};
 use axum::{http::StatusCode, Json};
 use serde_json::{json, Value};
-use std::cmp::Ordering;
 use std::sync::Arc;
 
-fn setup_finnhub_api(endpoint: Endpoint, api_token: &str) -> FinnhubAPI {
-    let mut finnhub_api = FinnhubAPI::new(api_token);
-    finnhub_api.endpoint(endpoint);
-    finnhub_api
-}
+// Every symbol costs one upstream call
+const MAX_SYMBOLS_PER_REQUEST: usize = 30;
 
 pub async fn get_market_news(
     State(state): State<Arc<AppState>>,
-) -> (StatusCode, Json<Vec<ArticleMarketNews>>) {
-    let fh_api = setup_finnhub_api(Endpoint::MarketNews, &state.api_token_finnhub);
-    let articles = fh_api
-        .fetch_market_news()
-        .await
-        .expect("The market news to be fetched");
-    (StatusCode::OK, Json(articles))
+) -> Result<(StatusCode, ServedBy, Json<Vec<ArticleMarketNews>>), ApiError> {
+    let (provider, articles) = state
+        .market_data
+        .serve(Operation::MarketNews, |provider| provider.market_news())
+        .await?;
+    Ok((StatusCode::OK, provider.into(), Json(articles)))
 }
 
 pub async fn get_company_news(
     State(state): State<Arc<AppState>>,
     query: Query<QueryCompanyNews>,
-) -> (StatusCode, Json<Vec<ArticleMarketNews>>) {
-    let fh_api = setup_finnhub_api(Endpoint::CompanyNews, &state.api_token_finnhub);
-    let articles = fh_api
-        .fetch_company_news(&query.0.symbol, &query.0.time_from, &query.0.time_to)
-        .await
-        .expect("The company news to be fetched");
-    (StatusCode::OK, Json(articles))
+) -> Result<(StatusCode, ServedBy, Json<Vec<ArticleMarketNews>>), ApiError> {
+    let QueryCompanyNews {
+        symbol,
+        time_from,
+        time_to,
+    } = &query.0;
+    let (provider, articles) = state
+        .market_data
+        .serve(Operation::CompanyNews, |provider| {
+            provider.company_news(symbol, time_from, time_to)
+        })
+        .await?;
+    Ok((StatusCode::OK, provider.into(), Json(articles)))
 }
 
 pub async fn get_quotes_for_index(
     Path(index): Path<String>,
     State(state): State<Arc<AppState>>,
-) -> (StatusCode, Json<Value>) {
-    let fh_api = setup_finnhub_api(Endpoint::Quote, &state.api_token_finnhub);
-
-    let market = match index.as_str() {
-        "djia" => DOW_JONES,
-        "nasdaq" => NASDAQ,
-        _ => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(json!( { "message": "Given index not valid. Try 'djia' or 'nasdaq'."})),
-            )
-        }
-    };
+) -> Result<(StatusCode, ServedBy, Json<QuoteSummary>), ApiError> {
+    let market = &index_definition(&state, &index)?.constituents;
 
     // 1) Get data for the given index and prepare it for the response
-    let quotes_extended = fh_api
-        .fetch_quotes_for_market(market)
-        .await
-        .expect("The market news to be fetched");
-
-    let quotes_extended_cloned = quotes_extended.clone();
+    let market_quotes = state.market_data.quotes(market).await?;
+    let served_by = ServedBy::quotes(&market_quotes);
+    let summary = summarize_quotes(market_quotes)?;
 
-    let last_quote = quotes_extended_cloned
-        .last()
-        .expect("the extended quotes array to have a last item.");
-
-    let mut lowest_rate_limit = quotes_extended
-        .iter()
-        .map(|q| {
-            q.rate_limit_info
-                .ratelimit_remaining
-                .parse::<u32>()
-                .unwrap()
-        })
-        .collect::<Vec<u32>>();
-
-    lowest_rate_limit.sort();
-
-    let quotes: Vec<SymbolQuoteFrontend> = quotes_extended
-        .into_iter()
-        .map(|q| SymbolQuoteFrontend {
-            current_price: q.current_price,
-            delta: q.delta,
-            delta_percent: q.delta_percent,
-            high: q.high,
-            low: q.low,
-            open: q.open,
-            previous_close: q.previous_close,
-            timestamp: q.timestamp,
-            symbol: q.symbol,
-            name: q.name,
-        })
-        .collect();
+    Ok((StatusCode::OK, served_by, Json(summary)))
+}
 
-    let mut quote_gainers: Vec<SymbolQuoteFrontend> = quotes
-        .iter()
-        .filter(|x| x.delta_percent.is_sign_positive())
-        .cloned()
-        .collect();
+pub fn index_definition<'a>(
+    state: &'a AppState,
+    index: &str,
+) -> Result<&'a IndexDefinition, ApiError> {
+    state.indices.get(index).ok_or_else(|| {
+        ApiError::InvalidInput(format!(
+            "Given index not valid. Try one of '{}'.",
+            state.indices.ids().join("', '")
+        ))
+    })
+}
 
-    // Sort descending
-    quote_gainers.sort_by(|a, b| b.delta_percent.partial_cmp(&a.delta_percent).unwrap());
+pub async fn get_quotes_for_symbols(
+    State(state): State<Arc<AppState>>,
+    query: Query<QuerySymbols>,
+) -> Result<(StatusCode, ServedBy, Json<Value>), ApiError> {
+    let mut market: Vec<Constituent> = Vec::new();
+    let mut invalid: Vec<&str> = Vec::new();
+
+    for symbol in query.0.symbols.split(',').filter(|s| !s.trim().is_empty()) {
+        match normalize_symbol(symbol) {
+            Some(symbol) if market.iter().all(|c| c.symbol != symbol) => market.push(Constituent {
+                name: state
+                    .indices
+                    .name_of(&symbol)
+                    .unwrap_or(&symbol)
+                    .to_string(),
+                symbol,
+            }),
+            Some(_) => {}
+            None => invalid.push(symbol.trim()),
+        }
+    }
+
+    if !invalid.is_empty() {
+        return Err(ApiError::InvalidInput(format!(
+            "Invalid symbols '{}'.",
+            invalid.join("', '")
+        )));
+    }
+    if market.is_empty() || market.len() > MAX_SYMBOLS_PER_REQUEST {
+        return Err(ApiError::InvalidInput(format!(
+            "Pass between 1 and {MAX_SYMBOLS_PER_REQUEST} comma separated symbols."
+        )));
+    }
+
+    let market_quotes = state.market_data.quotes(&market).await?;
+    let served_by = ServedBy::quotes(&market_quotes);
+    let batch = QuoteBatch::try_from(market_quotes)?;
+
+    Ok((
+        StatusCode::OK,
+        served_by,
+        Json(json!( {
+            "quotes": batch.quotes,
+            "failed": batch.failed,
+            "rate_limit_remaining": batch.rate_limit_remaining,
+            "rate_limit_reset": batch.rate_limit_reset,
+        } )),
+    ))
+}
 
-    let mut quote_losers: Vec<SymbolQuoteFrontend> = quotes
+pub async fn get_quote_for_symbol(
+    Path(symbol): Path<String>,
+    State(state): State<Arc<AppState>>,
+    query: Query<QueryQuoteInclude>,
+) -> Result<(StatusCode, ServedBy, Json<SymbolQuoteWithProfile>), ApiError> {
+    let symbol = normalize_symbol(&symbol)
+        .ok_or_else(|| ApiError::InvalidInput(format!("Invalid symbol '{symbol}'.")))?;
+
+    let mut include_profile = false;
+    for include in query
+        .0
+        .include
         .iter()
-        .filter(|x| x.delta_percent.is_sign_negative())
-        .cloned()
-        .collect();
-
-    // Sort ascending
-    quote_losers.sort_by(|a, b| a.delta_percent.partial_cmp(&b.delta_percent).unwrap());
-
-    let sentiment = match quote_gainers.len().cmp(&quote_losers.len()) {
-        Ordering::Greater => "bullish",
-        Ordering::Less => "bearish",
-        Ordering::Equal => "neutral",
-    };
-
-    let gains_percentage_sum: f32 = quote_gainers.iter().map(|q| q.delta_percent).sum();
-    let losses_percentage_sum: f32 = quote_losers.iter().map(|q| q.delta_percent).sum();
-    let avg_percentage_gains = gains_percentage_sum / quote_gainers.len() as f32;
-    let avg_percentage_losses = losses_percentage_sum / quote_losers.len() as f32;
+        .flat_map(|include| include.split(','))
+    {
+        match include.trim() {
+            "profile" => include_profile = true,
+            other => {
+                return Err(ApiError::InvalidInput(format!(
+                    "Unknown include '{other}'. Try 'profile'."
+                )))
+            }
+        }
+    }
+
+    let name = state.indices.name_of(&symbol).unwrap_or(&symbol);
+    let market_data = &state.market_data;
+
+    // Both requests run concurrently
+    let (quote, profile) = tokio::join!(
+        market_data.serve(Operation::Quote, |provider| provider.quote(&symbol, name)),
+        async {
+            if include_profile {
+                market_data
+                    .serve(Operation::CompanyProfile, |provider| {
+                        provider.company_profile(&symbol)
+                    })
+                    .await
+                    .map(Some)
+            } else {
+                Ok(None)
+            }
+        }
+    );
+    let (quote_provider, quote) = quote?;
+    let (profile_provider, profile) = profile?.unzip();
 
-    (
+    Ok((
         StatusCode::OK,
-        Json(json!( {
-            "sentiment": sentiment,
-            "avg_percentage_gains": avg_percentage_gains,
-            "avg_percentage_losses": avg_percentage_losses,
-            "gainers": quote_gainers,
-            "losers": quote_losers,
-            "rate_limit_remaining": lowest_rate_limit.first().expect("there to be a first entry in the rate limit remaining array"),
-            "rate_limit_reset": last_quote.rate_limit_info.ratelimit_reset.to_owned().parse::<u128>().unwrap(),
-        } )),
-    )
+        ServedBy(
+            Some(quote_provider)
+                .into_iter()
+                .chain(profile_provider)
+                .collect(),
+        ),
+        Json(SymbolQuoteWithProfile {
+            quote: quote.into(),
+            profile,
+        }),
+    ))
 }
 
 pub async fn get_company_profile(
     Path(symbol): Path<String>,
     State(state): State<Arc<AppState>>,
-) -> (StatusCode, Json<CompanyProfile>) {
-    let fh_api = setup_finnhub_api(Endpoint::CompanyProfile, &state.api_token_finnhub);
-
-    let company_profile = fh_api
-        .fetch_company_profile(&symbol)
-        .await
-        .expect("the company profile to be fetched.");
+) -> Result<(StatusCode, ServedBy, Json<CompanyProfile>), ApiError> {
+    let (provider, company_profile) = state
+        .market_data
+        .serve(Operation::CompanyProfile, |provider| {
+            provider.company_profile(&symbol)
+        })
+        .await?;
 
-    (StatusCode::OK, Json(company_profile))
+    Ok((StatusCode::OK, provider.into(), Json(company_profile)))
 }
 
 pub async fn get_social_sentiment(
     State(state): State<Arc<AppState>>,
     query: Query<QuerySocialSentiment>,
-) -> (StatusCode, Json<SocialSentimentResponse>) {
-    let fh_api = setup_finnhub_api(Endpoint::SocialSentiment, &state.api_token_finnhub);
-    let url_add = format!("?symbol={}&from={}", query.0.symbol, query.0.time_from);
-
-    let social_sentiment = fh_api
-        .fetch_social_sentiment(&url_add)
-        .await
-        .expect("the social sentiment to be fetched.");
+) -> Result<(StatusCode, ServedBy, Json<SocialSentimentResponse>), ApiError> {
+    let QuerySocialSentiment { symbol, time_from } = &query.0;
+    let (provider, social_sentiment) = state
+        .market_data
+        .serve(Operation::SocialSentiment, |provider| {
+            provider.social_sentiment(symbol, time_from)
+        })
+        .await?;
 
-    (StatusCode::OK, Json(social_sentiment))
+    Ok((StatusCode::OK, provider.into(), Json(social_sentiment)))
 }
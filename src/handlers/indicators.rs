@@ -0,0 +1,243 @@
+use crate::candles::{unix_now, Candle, CandleRange, Resolution};
+use crate::error::{ApiError, Provider};
+use crate::handlers::candles::fetch_candles;
+use crate::indicators::{self, BollingerBands, Macd};
+use crate::indices::normalize_symbol;
+use crate::provider::ServedBy;
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const MAX_INDICATORS: usize = 10;
+const MAX_PERIOD: usize = 200;
+const MAX_DAYS: usize = 365;
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/*
+ * ?set=rsi14,sma50,macd&days=100
+ */
+#[derive(Deserialize)]
+pub struct QueryIndicators {
+    pub set: String,
+    pub days: Option<usize>,
+}
+
+/**
+ * A single indicator of the `set` query parameter, e.g. "rsi14" or "macd".
+ */
+#[derive(Debug, Clone, Copy)]
+enum Indicator {
+    Sma(usize),
+    Ema(usize),
+    Rsi(usize),
+    /**
+     * Always 12, 26 and 9 days
+     */
+    Macd,
+    /**
+     * With two standard deviations
+     */
+    Bollinger(usize),
+    Atr(usize),
+}
+
+impl Indicator {
+    fn parse(spec: &str) -> Option<Indicator> {
+        let split = spec
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(spec.len());
+        let (name, period) = spec.split_at(split);
+        let period = match period {
+            "" => None,
+            period => Some(period.parse::<usize>().ok()?),
+        };
+
+        let indicator = match (name, period) {
+            ("sma", period) => Self::Sma(period.unwrap_or(20)),
+            ("ema", period) => Self::Ema(period.unwrap_or(20)),
+            ("rsi", period) => Self::Rsi(period.unwrap_or(14)),
+            ("macd", None) => Self::Macd,
+            ("bb", period) => Self::Bollinger(period.unwrap_or(20)),
+            ("atr", period) => Self::Atr(period.unwrap_or(14)),
+            _ => return None,
+        };
+
+        (2..=MAX_PERIOD)
+            .contains(&indicator.period())
+            .then_some(indicator)
+    }
+
+    fn period(&self) -> usize {
+        match self {
+            Self::Sma(period)
+            | Self::Ema(period)
+            | Self::Rsi(period)
+            | Self::Bollinger(period)
+            | Self::Atr(period) => *period,
+            Self::Macd => 26,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::Sma(period) => format!("sma{period}"),
+            Self::Ema(period) => format!("ema{period}"),
+            Self::Rsi(period) => format!("rsi{period}"),
+            Self::Macd => "macd".to_string(),
+            Self::Bollinger(period) => format!("bb{period}"),
+            Self::Atr(period) => format!("atr{period}"),
+        }
+    }
+
+    /**
+     * Days of history needed before the first returned value. Smoothed indicators get
+     * three times their period to converge.
+     */
+    fn warm_up(&self) -> usize {
+        match self {
+            Self::Sma(period) | Self::Bollinger(period) => *period,
+            Self::Ema(period) | Self::Rsi(period) | Self::Atr(period) => 3 * period,
+            Self::Macd => 3 * 26 + 9,
+        }
+    }
+
+    fn compute(&self, candles: &[Candle]) -> Vec<Option<IndicatorValue>> {
+        let closes: Vec<f64> = candles.iter().map(|candle| candle.close).collect();
+        let single = |values: Vec<Option<f64>>| -> Vec<Option<IndicatorValue>> {
+            values
+                .into_iter()
+                .map(|value| value.map(|value| IndicatorValue::Single { value }))
+                .collect()
+        };
+
+        match self {
+            Self::Sma(period) => single(indicators::sma(&closes, *period)),
+            Self::Ema(period) => single(indicators::ema(&closes, *period)),
+            Self::Rsi(period) => single(indicators::rsi(&closes, *period)),
+            Self::Macd => indicators::macd(&closes, 12, 26, 9)
+                .into_iter()
+                .map(|value| value.map(IndicatorValue::Macd))
+                .collect(),
+            Self::Bollinger(period) => indicators::bollinger_bands(&closes, *period, 2.0)
+                .into_iter()
+                .map(|value| value.map(IndicatorValue::Bollinger))
+                .collect(),
+            Self::Atr(period) => {
+                let highs: Vec<f64> = candles.iter().map(|candle| candle.high).collect();
+                let lows: Vec<f64> = candles.iter().map(|candle| candle.low).collect();
+                single(indicators::atr(&highs, &lows, &closes, *period))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum IndicatorValue {
+    Single { value: f64 },
+    Macd(Macd),
+    Bollinger(BollingerBands),
+}
+
+#[derive(Serialize, Debug)]
+pub struct IndicatorPoint {
+    timestamp: i64,
+    #[serde(flatten)]
+    value: IndicatorValue,
+}
+
+#[derive(Serialize, Debug)]
+pub struct IndicatorsResponse {
+    symbol: String,
+    provider: Provider,
+    /**
+     * Daily values of every requested indicator, keyed by its name (e.g. "rsi14")
+     */
+    indicators: BTreeMap<String, Vec<IndicatorPoint>>,
+}
+
+pub async fn get_indicators(
+    Path(symbol): Path<String>,
+    State(state): State<Arc<AppState>>,
+    query: Query<QueryIndicators>,
+) -> Result<(StatusCode, ServedBy, Json<IndicatorsResponse>), ApiError> {
+    let symbol = normalize_symbol(&symbol)
+        .ok_or_else(|| ApiError::InvalidInput(format!("Invalid symbol '{symbol}'.")))?;
+
+    let mut set: Vec<Indicator> = Vec::new();
+    for spec in query
+        .0
+        .set
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+    {
+        let indicator = Indicator::parse(&spec.to_lowercase()).ok_or_else(|| {
+            ApiError::InvalidInput(format!(
+                "Invalid indicator '{spec}'. Use sma, ema, rsi, bb or atr with a period between \
+                 2 and {MAX_PERIOD} (e.g. 'rsi14'), or 'macd'."
+            ))
+        })?;
+        set.push(indicator);
+    }
+    if set.is_empty() || set.len() > MAX_INDICATORS {
+        return Err(ApiError::InvalidInput(format!(
+            "Pass between 1 and {MAX_INDICATORS} comma separated indicators."
+        )));
+    }
+
+    let days = query.0.days.unwrap_or(100);
+    if !(1..=MAX_DAYS).contains(&days) {
+        return Err(ApiError::InvalidInput(format!(
+            "'days' must be between 1 and {MAX_DAYS}."
+        )));
+    }
+
+    // Trading days to calendar days, with some slack for holidays
+    let warm_up = set.iter().map(Indicator::warm_up).max().unwrap_or_default();
+    let calendar_days = ((days + warm_up) * 7 / 5 + 10) as i64;
+    let now = unix_now();
+    let range = CandleRange {
+        resolution: Resolution::Day,
+        from: now - calendar_days * SECONDS_PER_DAY,
+        to: now,
+    };
+
+    let (provider, candles) = fetch_candles(&state, &symbol, &range).await?;
+
+    let indicators = set
+        .iter()
+        .map(|indicator| {
+            let values = indicator.compute(&candles);
+            let points: Vec<IndicatorPoint> = candles
+                .iter()
+                .zip(values)
+                .filter_map(|(candle, value)| {
+                    Some(IndicatorPoint {
+                        timestamp: candle.timestamp,
+                        value: value?,
+                    })
+                })
+                .collect();
+            let skip = points.len().saturating_sub(days);
+
+            (
+                indicator.name(),
+                points.into_iter().skip(skip).collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        provider.into(),
+        Json(IndicatorsResponse {
+            symbol,
+            provider,
+            indicators,
+        }),
+    ))
+}
@@ -0,0 +1,23 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde_json::{json, Value};
+
+use crate::AppState;
+
+pub async fn get_indices(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Vec<Value>>) {
+    let indices = state
+        .indices
+        .iter()
+        .map(|index| {
+            json!({
+                "id": index.id,
+                "name": index.name,
+                "version": index.version,
+                "constituents": index.constituents.len(),
+            })
+        })
+        .collect();
+
+    (StatusCode::OK, Json(indices))
+}
@@ -1,2 +1,9 @@
 pub mod alphavantage;
+pub mod candles;
 pub mod finnhub;
+pub mod indicators;
+pub mod indices;
+pub mod status;
+pub mod stream;
+pub mod trades;
+pub mod watchlists;
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderName, StatusCode},
+    Json,
+};
+use serde_json::{json, Value};
+
+use crate::{metrics, AppState};
+
+/**
+ * Liveness, the process is up and serving requests.
+ */
+pub async fn get_healthz() -> (StatusCode, Json<Value>) {
+    (StatusCode::OK, Json(json!({ "status": "ok" })))
+}
+
+/**
+ * Readiness, at least one data provider is reachable. The config was already validated on
+ * startup. The checks don't use up any rate limit or quota.
+ */
+pub async fn get_readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Value>) {
+    let (finnhub, alphavantage) = tokio::join!(state.finnhub.ping(), state.alphavantage.ping());
+
+    let ready = finnhub.is_ok() || alphavantage.is_ok();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let reachability = |result: Result<(), String>| match result {
+        Ok(()) => json!({ "reachable": true }),
+        Err(message) => json!({ "reachable": false, "error": message }),
+    };
+
+    (
+        status,
+        Json(json!({
+            "status": if ready { "ready" } else { "unavailable" },
+            "providers": {
+                "finnhub": reachability(finnhub.map_err(|err| err.to_string())),
+                "alphavantage": reachability(alphavantage.map_err(|err| err.to_string())),
+            },
+        })),
+    )
+}
+
+/**
+ * Latest upstream call results per provider with the Finnhub rate limit and the Alpha
+ * Vantage daily quota.
+ */
+pub async fn get_status(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Value>) {
+    let finnhub_health = state.finnhub.health();
+    let alphavantage_health = state.alphavantage.health();
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "finnhub": {
+                "last_success": finnhub_health.last_success,
+                "last_error": finnhub_health.last_error,
+                "rate_limit": state.finnhub.rate_limit_info(),
+            },
+            "alphavantage": {
+                "last_success": alphavantage_health.last_success,
+                "last_error": alphavantage_health.last_error,
+                "daily_quota": state.alphavantage.quota_usage(),
+            },
+        })),
+    )
+}
+
+/**
+ * Request, upstream and cache metrics in the Prometheus text format.
+ */
+pub async fn get_metrics(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, [(HeaderName, &'static str); 1], String) {
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(&state),
+    )
+}
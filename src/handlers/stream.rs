@@ -0,0 +1,36 @@
+use crate::error::ApiError;
+use crate::handlers::finnhub::index_definition;
+use crate::quote_stream::StreamEvent;
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio_stream::{Stream, StreamExt};
+
+/**
+ * Server-Sent Events with the quotes of an index. Starts with a `snapshot` event in the
+ * shape of `/quotes/:index`, followed by `update` events with only the changed quotes and
+ * `error` events if a poll failed for every symbol.
+ */
+pub async fn stream_quotes_for_index(
+    Path(index): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let constituents = &index_definition(&state, &index)?.constituents;
+    let subscription = state.quote_streams.subscribe(&state, &index, constituents);
+
+    let events = subscription.into_stream().filter_map(|event| {
+        let event = match event {
+            StreamEvent::Snapshot(summary) => {
+                Event::default().event("snapshot").json_data(&*summary)
+            }
+            StreamEvent::Update(update) => Event::default().event("update").json_data(&*update),
+            StreamEvent::Error(error) => Event::default().event("error").json_data(&*error),
+        };
+        // Serializing the quotes can't fail, skip the event if it does anyway
+        event.ok().map(Ok)
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
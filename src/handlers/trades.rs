@@ -0,0 +1,78 @@
+use crate::indices::normalize_symbol;
+use crate::trades::TradesClient;
+use crate::AppState;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/*
+ * Sent by the clients, e.g. { "action": "subscribe", "symbol": "AAPL" }
+ */
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum ClientMessage {
+    Subscribe { symbol: String },
+    Unsubscribe { symbol: String },
+}
+
+pub async fn get_trades_socket(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let client = state.trades.connect();
+    ws.on_upgrade(move |socket| handle_trades_socket(socket, client))
+}
+
+async fn handle_trades_socket(mut socket: WebSocket, mut client: TradesClient) {
+    loop {
+        let reply = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => handle_client_message(&client, &text).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            trades = client.trades.recv() => match trades {
+                Some(trades) => json!({ "type": "trades", "data": trades }),
+                None => return,
+            },
+        };
+
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn handle_client_message(client: &TradesClient, text: &str) -> Value {
+    let message = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(message) => message,
+        Err(_) => {
+            return error_message(
+                "Expected { \"action\": \"subscribe\" | \"unsubscribe\", \"symbol\": \"...\" }",
+            )
+        }
+    };
+
+    let (ClientMessage::Subscribe { symbol } | ClientMessage::Unsubscribe { symbol }) = &message;
+    let Some(symbol) = normalize_symbol(symbol) else {
+        return error_message(&format!("'{symbol}' is not a valid stock symbol"));
+    };
+
+    match message {
+        ClientMessage::Subscribe { .. } => match client.subscribe(&symbol).await {
+            Ok(()) => json!({ "type": "subscribed", "symbol": symbol }),
+            Err(err) => error_message(&err.to_string()),
+        },
+        ClientMessage::Unsubscribe { .. } => {
+            client.unsubscribe(&symbol);
+            json!({ "type": "unsubscribed", "symbol": symbol })
+        }
+    }
+}
+
+fn error_message(message: &str) -> Value {
+    json!({ "type": "error", "message": message })
+}
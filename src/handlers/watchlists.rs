@@ -0,0 +1,116 @@
+use crate::error::ApiError;
+use crate::indices::Constituent;
+use crate::provider::ServedBy;
+use crate::quotes::{summarize_quotes, QuoteSummary};
+use crate::watchlists::Watchlist;
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::{http::StatusCode, Json};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct CreateWatchlist {
+    pub name: String,
+    #[serde(default)]
+    pub symbols: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RenameWatchlist {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct AddSymbols {
+    pub symbols: Vec<String>,
+}
+
+pub async fn get_watchlists(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<Vec<Watchlist>>) {
+    (StatusCode::OK, Json(state.watchlists.list().await))
+}
+
+pub async fn create_watchlist(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<CreateWatchlist>,
+) -> Result<(StatusCode, Json<Watchlist>), ApiError> {
+    let watchlist = state.watchlists.create(&body.name, &body.symbols).await?;
+    Ok((StatusCode::CREATED, Json(watchlist)))
+}
+
+pub async fn get_watchlist(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<Watchlist>), ApiError> {
+    let watchlist = state.watchlists.get(id).await?;
+    Ok((StatusCode::OK, Json(watchlist)))
+}
+
+pub async fn rename_watchlist(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<RenameWatchlist>,
+) -> Result<(StatusCode, Json<Watchlist>), ApiError> {
+    let watchlist = state.watchlists.rename(id, &body.name).await?;
+    Ok((StatusCode::OK, Json(watchlist)))
+}
+
+pub async fn delete_watchlist(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, ApiError> {
+    state.watchlists.delete(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn add_watchlist_symbols(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<AddSymbols>,
+) -> Result<(StatusCode, Json<Watchlist>), ApiError> {
+    let watchlist = state.watchlists.add_symbols(id, &body.symbols).await?;
+    Ok((StatusCode::OK, Json(watchlist)))
+}
+
+pub async fn remove_watchlist_symbol(
+    Path((id, symbol)): Path<(u64, String)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<Watchlist>), ApiError> {
+    let watchlist = state.watchlists.remove_symbol(id, &symbol).await?;
+    Ok((StatusCode::OK, Json(watchlist)))
+}
+
+pub async fn get_quotes_for_watchlist(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, ServedBy, Json<QuoteSummary>), ApiError> {
+    let watchlist = state.watchlists.get(id).await?;
+    if watchlist.symbols.is_empty() {
+        return Err(ApiError::InvalidInput(format!(
+            "The watchlist '{}' has no symbols.",
+            watchlist.name
+        )));
+    }
+
+    // Company names are only known for index constituents
+    let market: Vec<Constituent> = watchlist
+        .symbols
+        .into_iter()
+        .map(|symbol| Constituent {
+            name: state
+                .indices
+                .name_of(&symbol)
+                .unwrap_or(&symbol)
+                .to_string(),
+            symbol,
+        })
+        .collect();
+
+    let market_quotes = state.market_data.quotes(&market).await?;
+    let served_by = ServedBy::quotes(&market_quotes);
+    let summary = summarize_quotes(market_quotes)?;
+
+    Ok((StatusCode::OK, served_by, Json(summary)))
+}
@@ -0,0 +1,73 @@
+use std::{collections::BTreeMap, fmt::Display, sync::Mutex};
+
+use serde::Serialize;
+
+use crate::candles::unix_now;
+
+/**
+ * Implemented by the upstream API errors to count them by variant.
+ */
+pub trait ErrorKind: Display {
+    /**
+     * Variant name in snake case, e.g. "rate_limited"
+     */
+    fn kind(&self) -> &'static str;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LastError {
+    /**
+     * UNIX timestamp
+     */
+    pub at: i64,
+    pub message: String,
+}
+
+/**
+ * Outcome of the latest upstream calls of a provider, shared by all clones of its client.
+ */
+#[derive(Debug, Default)]
+pub struct ProviderHealth {
+    state: Mutex<ProviderHealthSnapshot>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProviderHealthSnapshot {
+    /**
+     * UNIX timestamp of the latest successful upstream call
+     */
+    pub last_success: Option<i64>,
+    pub last_error: Option<LastError>,
+    /**
+     * Number of errors by kind
+     */
+    pub errors: BTreeMap<&'static str, u64>,
+}
+
+impl ProviderHealth {
+    /**
+     * Records the result of an upstream call, after all retries.
+     */
+    pub fn record<T, E: ErrorKind>(&self, result: &Result<T, E>) {
+        match result {
+            Ok(_) => self.state.lock().unwrap().last_success = Some(unix_now()),
+            Err(err) => self.record_error(err),
+        }
+    }
+
+    /**
+     * Records an error of a call whose request succeeded, e.g. an invalid response body.
+     */
+    pub fn record_error<E: ErrorKind>(&self, err: &E) {
+        let mut state = self.state.lock().unwrap();
+        state.last_error = Some(LastError {
+            at: unix_now(),
+            message: err.to_string(),
+        });
+        *state.errors.entry(err.kind()).or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> ProviderHealthSnapshot {
+        self.state.lock().unwrap().clone()
+    }
+}
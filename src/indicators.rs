@@ -0,0 +1,299 @@
+use serde::Serialize;
+
+/*
+ * Technical indicators over price series sorted by time.
+ *
+ * Every function returns one entry per input value, `None` while the indicator is still
+ * warming up (e.g. the first 49 values of a 50 day SMA).
+ */
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct Macd {
+    pub macd: f64,
+    pub signal: f64,
+    pub histogram: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct BollingerBands {
+    pub upper: f64,
+    pub middle: f64,
+    pub lower: f64,
+}
+
+/**
+ * Simple moving average.
+ */
+pub fn sma(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut result = vec![None; values.len()];
+    if period == 0 || values.len() < period {
+        return result;
+    }
+
+    let mut sum: f64 = values[..period].iter().sum();
+    result[period - 1] = Some(sum / period as f64);
+
+    for i in period..values.len() {
+        sum += values[i] - values[i - period];
+        result[i] = Some(sum / period as f64);
+    }
+
+    result
+}
+
+/**
+ * Exponential moving average with a smoothing factor of `2 / (period + 1)`, seeded with the
+ * SMA of the first `period` values.
+ */
+pub fn ema(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    smoothed(values, period, 2.0 / (period as f64 + 1.0))
+}
+
+/**
+ * Moving average seeded with the SMA of the first `period` values, then
+ * `previous + alpha * (value - previous)`.
+ */
+fn smoothed(values: &[f64], period: usize, alpha: f64) -> Vec<Option<f64>> {
+    let mut result = vec![None; values.len()];
+    if period == 0 || values.len() < period {
+        return result;
+    }
+
+    let mut average = values[..period].iter().sum::<f64>() / period as f64;
+    result[period - 1] = Some(average);
+
+    for i in period..values.len() {
+        average += alpha * (values[i] - average);
+        result[i] = Some(average);
+    }
+
+    result
+}
+
+/**
+ * Relative strength index with Wilder's smoothing of the average gains and losses.
+ */
+pub fn rsi(closes: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut result = vec![None; closes.len()];
+    if period == 0 || closes.len() <= period {
+        return result;
+    }
+
+    let changes: Vec<f64> = closes.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let gains: Vec<f64> = changes.iter().map(|change| change.max(0.0)).collect();
+    let losses: Vec<f64> = changes.iter().map(|change| (-change).max(0.0)).collect();
+
+    let alpha = 1.0 / period as f64;
+    let average_gains = smoothed(&gains, period, alpha);
+    let average_losses = smoothed(&losses, period, alpha);
+
+    for (i, (gain, loss)) in average_gains.iter().zip(&average_losses).enumerate() {
+        if let (Some(gain), Some(loss)) = (gain, loss) {
+            // The changes start at the second close
+            result[i + 1] = Some(if *loss == 0.0 {
+                100.0
+            } else {
+                100.0 - 100.0 / (1.0 + gain / loss)
+            });
+        }
+    }
+
+    result
+}
+
+/**
+ * Moving average convergence divergence: the `fast` EMA minus the `slow` EMA, with a
+ * `signal` EMA of that difference.
+ */
+pub fn macd(closes: &[f64], fast: usize, slow: usize, signal: usize) -> Vec<Option<Macd>> {
+    let fast_ema = ema(closes, fast);
+    let slow_ema = ema(closes, slow);
+
+    let macd_line: Vec<Option<f64>> = fast_ema
+        .iter()
+        .zip(&slow_ema)
+        .map(|(fast, slow)| Some((*fast)? - (*slow)?))
+        .collect();
+
+    // The signal line starts once the MACD line has enough values
+    let start = macd_line.iter().position(Option::is_some);
+    let mut result = vec![None; closes.len()];
+    let Some(start) = start else {
+        return result;
+    };
+
+    let defined: Vec<f64> = macd_line[start..].iter().flatten().copied().collect();
+    let signal_line = ema(&defined, signal);
+
+    for (offset, signal) in signal_line.into_iter().enumerate() {
+        if let Some(signal) = signal {
+            let macd = defined[offset];
+            result[start + offset] = Some(Macd {
+                macd,
+                signal,
+                histogram: macd - signal,
+            });
+        }
+    }
+
+    result
+}
+
+/**
+ * Bollinger Bands: the SMA plus/minus `deviations` times the (population) standard deviation.
+ */
+pub fn bollinger_bands(
+    closes: &[f64],
+    period: usize,
+    deviations: f64,
+) -> Vec<Option<BollingerBands>> {
+    sma(closes, period)
+        .into_iter()
+        .enumerate()
+        .map(|(i, middle)| {
+            let middle = middle?;
+            let window = &closes[i + 1 - period..=i];
+            let variance = window
+                .iter()
+                .map(|close| (close - middle).powi(2))
+                .sum::<f64>()
+                / period as f64;
+            let width = deviations * variance.sqrt();
+
+            Some(BollingerBands {
+                upper: middle + width,
+                middle,
+                lower: middle - width,
+            })
+        })
+        .collect()
+}
+
+/**
+ * Average true range with Wilder's smoothing. The true range of the first candle is its
+ * high minus its low.
+ */
+pub fn atr(highs: &[f64], lows: &[f64], closes: &[f64], period: usize) -> Vec<Option<f64>> {
+    let true_ranges: Vec<f64> = (0..closes.len())
+        .map(|i| {
+            let range = highs[i] - lows[i];
+            match i.checked_sub(1).map(|previous| closes[previous]) {
+                Some(previous_close) => range
+                    .max((highs[i] - previous_close).abs())
+                    .max((lows[i] - previous_close).abs()),
+                None => range,
+            }
+        })
+        .collect();
+
+    smoothed(&true_ranges, period, 1.0 / period as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f64 = 0.006;
+
+    // https://school.stockcharts.com/doku.php?id=technical_indicators:moving_averages
+    const EMA_CLOSES: [f64; 30] = [
+        22.27, 22.19, 22.08, 22.17, 22.18, 22.13, 22.23, 22.43, 22.24, 22.29, 22.15, 22.39, 22.38,
+        22.61, 23.36, 24.05, 23.75, 23.83, 23.95, 23.63, 23.82, 23.87, 23.65, 23.19, 23.10, 23.33,
+        22.68, 23.10, 22.40, 22.17,
+    ];
+
+    // https://school.stockcharts.com/doku.php?id=technical_indicators:relative_strength_index_rsi
+    const RSI_CLOSES: [f64; 27] = [
+        44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03, 45.61,
+        46.28, 46.28, 46.00, 46.03, 46.41, 46.22, 45.64, 46.21, 46.25, 45.71, 46.45, 45.78, 45.35,
+        44.03,
+    ];
+
+    fn assert_close(actual: &[Option<f64>], expected: &[f64]) {
+        let actual: Vec<f64> = actual.iter().flatten().copied().collect();
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < TOLERANCE,
+                "expected {expected}, got {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn sma_averages_the_window() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(
+            sma(&values, 3),
+            vec![None, None, Some(2.0), Some(3.0), Some(4.0), Some(5.0)]
+        );
+        assert!(sma(&values, 7).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn ema_matches_reference() {
+        let expected = [
+            22.22, 22.21, 22.24, 22.27, 22.33, 22.52, 22.80, 22.97, 23.13, 23.28, 23.34, 23.43,
+            23.51, 23.53, 23.47, 23.40, 23.39, 23.26, 23.23, 23.08, 22.92,
+        ];
+        let result = ema(&EMA_CLOSES, 10);
+        assert!(result[..9].iter().all(Option::is_none));
+        assert_close(&result, &expected);
+    }
+
+    #[test]
+    fn rsi_matches_reference() {
+        let expected = [
+            70.46, 66.25, 66.48, 69.35, 66.29, 57.92, 62.88, 63.21, 56.01, 62.34, 54.67, 50.39,
+            40.02,
+        ];
+        let result = rsi(&RSI_CLOSES, 14);
+        assert!(result[..14].iter().all(Option::is_none));
+        assert_close(&result, &expected);
+    }
+
+    #[test]
+    fn rsi_is_100_without_losses() {
+        let closes: Vec<f64> = (1..=20).map(f64::from).collect();
+        assert_eq!(rsi(&closes, 14).last(), Some(&Some(100.0)));
+    }
+
+    #[test]
+    fn macd_of_linear_series_is_the_ema_lag_difference() {
+        // The EMA of a linear series lags (period - 1) / 2 behind, so the MACD is
+        // (26 - 1) / 2 - (12 - 1) / 2 = 7 with a flat signal line
+        let closes: Vec<f64> = (0..60).map(f64::from).collect();
+        let result = macd(&closes, 12, 26, 9);
+
+        // The slow EMA starts at 25, the signal line 8 values later
+        assert!(result[..33].iter().all(Option::is_none));
+        for value in result[33..].iter().flatten() {
+            assert!((value.macd - 7.0).abs() < 1e-9);
+            assert!((value.signal - 7.0).abs() < 1e-9);
+            assert!(value.histogram.abs() < 1e-9);
+        }
+        assert_eq!(result[33..].iter().flatten().count(), 27);
+    }
+
+    #[test]
+    fn bollinger_bands_use_population_standard_deviation() {
+        let result = bollinger_bands(&[1.0, 2.0, 3.0, 4.0, 5.0], 5, 2.0);
+        let bands = result[4].unwrap();
+
+        assert!(result[..4].iter().all(Option::is_none));
+        assert_eq!(bands.middle, 3.0);
+        assert!((bands.upper - (3.0 + 2.0 * 2f64.sqrt())).abs() < 1e-9);
+        assert!((bands.lower - (3.0 - 2.0 * 2f64.sqrt())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn atr_includes_gaps_in_the_true_range() {
+        let highs = [10.0, 11.0, 12.0, 11.0, 13.0, 16.0];
+        let lows = [8.0, 9.0, 10.0, 9.0, 10.0, 15.0];
+        let closes = [9.0, 10.0, 11.0, 10.0, 12.0, 15.5];
+
+        // True ranges 2, 2, 2, 2, 3 and 4 (gap up from 12 to 16)
+        let expected = [2.0, 2.0, 7.0 / 3.0, 26.0 / 9.0];
+        assert_close(&atr(&highs, &lows, &closes, 3), &expected);
+    }
+}
@@ -1,116 +1,167 @@
-pub enum Index {
-    DowJones,
-    NASDAQ,
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * Index definitions compiled into the binary, see `data/indices`.
+ */
+const EMBEDDED_INDICES: &[(&str, &str)] = &[
+    ("djia.json", include_str!("../data/indices/djia.json")),
+    ("nasdaq.json", include_str!("../data/indices/nasdaq.json")),
+];
+
+const MAX_SYMBOL_LENGTH: usize = 12;
+
+/**
+ * Uppercases a stock symbol like "brk.b" and checks it only contains letters, digits and
+ * `.`, `-` or `:`. Returns `None` for anything which can't be a symbol.
+ */
+pub fn normalize_symbol(symbol: &str) -> Option<String> {
+    let symbol = symbol.trim().to_uppercase();
+    let valid = !symbol.is_empty()
+        && symbol.len() <= MAX_SYMBOL_LENGTH
+        && symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':'));
+
+    valid.then_some(symbol)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum IndexError {
+    #[error("Failed reading the index definitions from {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed parsing the index definition {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("The index definition {path} has no constituents")]
+    Empty { path: PathBuf },
 }
 
-trait AsStr {
-    fn as_str(&self) -> &str;
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Constituent {
+    pub symbol: String,
+    pub name: String,
 }
 
-impl ToString for Index {
-    fn to_string(&self) -> String {
-        match self {
-            Self::DowJones => "djia".to_string(),
-            Self::NASDAQ => "nasdq".to_string(),
+/**
+ * An index (or any other basket of symbols) loaded from a JSON file like
+ * `data/indices/djia.json`. `version` is the date the constituents were last checked.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexDefinition {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub constituents: Vec<Constituent>,
+}
+
+impl IndexDefinition {
+    fn parse(path: &Path, json: &str) -> Result<IndexDefinition, IndexError> {
+        let definition: IndexDefinition =
+            serde_json::from_str(json).map_err(|source| IndexError::Parse {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        if definition.constituents.is_empty() {
+            return Err(IndexError::Empty {
+                path: path.to_path_buf(),
+            });
         }
+
+        Ok(definition)
     }
 }
 
-impl AsStr for Index {
-    fn as_str(&self) -> &str {
-        match self {
-            Self::DowJones => "djia",
-            Self::NASDAQ => "nasdq",
+/**
+ * All indices the quotes can be requested for, keyed by their id (e.g. "djia").
+ */
+#[derive(Debug, Clone, Default)]
+pub struct IndexRegistry {
+    indices: BTreeMap<String, IndexDefinition>,
+}
+
+impl IndexRegistry {
+    /**
+     * Registry with the index definitions compiled into the binary.
+     */
+    pub fn embedded() -> IndexRegistry {
+        let mut registry = IndexRegistry::default();
+
+        for (file_name, json) in EMBEDDED_INDICES {
+            let definition = IndexDefinition::parse(Path::new(file_name), json)
+                .expect("the embedded index definitions to be valid");
+            registry.insert(definition);
         }
+
+        registry
     }
-}
 
-// 30 entries
-pub const DOW_JONES: &[(&str, &str)] = &[
-    ("JPM", "JPMorgan Chase & Co"),
-    ("WBA", "Walgreens Boots Alliance Inc"),
-    ("PG", "Procter & Gamble Co"),
-    ("GS", "Goldman Sachs Group Inc"),
-    ("AAPL", "Apple Inc"),
-    ("CAT", "Caterpillar Inc"),
-    ("BA", "Boeing Co"),
-    ("HON", "Honeywell International Inc"),
-    ("MRK", "Merck & Co Inc"),
-    ("MMM", "3M Co"),
-    ("IBM", "International Business Machines Corp"),
-    ("DIS", "Walt Disney Co"),
-    ("HD", "Home Depot Inc"),
-    ("AMGN", "Amgen Inc"),
-    ("V", "Visa Inc"),
-    ("INTC", "Intel Corp"),
-    ("DOW", "Dow Inc"),
-    ("MSFT", "Microsoft Corp"),
-    ("TRV", "Travelers Companies Inc"),
-    ("JNJ", "Johnson & Johnson"),
-    ("KO", "Coca-Cola Co"),
-    ("NKE", "Nike Inc"),
-    ("MCD", "McDonald's Corp"),
-    ("WMT", "Walmart Inc"),
-    ("CRM", "Salesforce Inc"),
-    ("AXP", "American Express Co"),
-    ("UNH", "UnitedHealth Group Inc"),
-    ("CVX", "Chevron Corp"),
-    ("VZ", "Verizon Communications Inc"),
-    ("CSCO", "Cisco Systems Inc"),
-];
+    /**
+     * Embedded registry extended with every `*.json` file in the given directory. Files
+     * replace embedded indices with the same id.
+     */
+    pub fn load_dir(dir: &Path) -> Result<IndexRegistry, IndexError> {
+        let io_error = |source| IndexError::Io {
+            path: dir.to_path_buf(),
+            source,
+        };
+        let mut registry = IndexRegistry::embedded();
 
-// 52 entries
-pub const NASDAQ: &[(&str, &str)] = &[
-    ("AMZN", "Amazon.com, Inc."),
-    ("META", "Meta Platforms Inc."),
-    ("TSLA", "Tesla, Inc."),
-    ("NVDA", "NVIDIA Corporation"),
-    ("PYPL", "PayPal Holdings, Inc."),
-    ("ASML", "ASML Holding N.V."),
-    ("ZM", "Zoom Video Communications, Inc."),
-    ("MRNA", "Moderna, Inc."),
-    ("VRTX", "Vertex Pharmaceuticals Incorporated"),
-    ("REGN", "Regeneron Pharmaceuticals, Inc."),
-    ("ILMN", "Illumina, Inc."),
-    ("JD", "JD.com, Inc."),
-    ("BIDU", "Baidu, Inc."),
-    ("MELI", "MercadoLibre, Inc."),
-    ("DOCU", "DocuSign, Inc."),
-    ("PDD", "Pinduoduo Inc."),
-    ("FSLY", "Fastly, Inc."),
-    ("TEAM", "Atlassian Corporation Plc"),
-    ("ALGN", "Align Technology, Inc."),
-    ("MRVL", "Marvell Technology Group Ltd."),
-    ("ZI", "ZoomInfo Technologies Inc."),
-    ("CDW", "CDW Corporation"),
-    ("CCI", "Crown Castle International Corp."),
-    ("MSCI", "MSCI Inc."),
-    ("NTES", "NetEase, Inc."),
-    ("IDXX", "IDEXX Laboratories, Inc."),
-    ("SGEN", "Seagen Inc."),
-    ("OKTA", "Okta, Inc."),
-    ("MTCH", "Match Group, Inc."),
-    ("VRSK", "Verisk Analytics, Inc."),
-    ("LULU", "Lululemon Athletica Inc."),
-    ("MNST", "Monster Beverage Corporation"),
-    ("KLAC", "KLA Corporation"),
-    ("ANSS", "ANSYS, Inc."),
-    ("PAYC", "Paycom Software, Inc."),
-    ("RMD", "ResMed Inc."),
-    ("CDNS", "Cadence Design Systems, Inc."),
-    ("CHKP", "Check Point Software Technologies Ltd."),
-    ("SWKS", "Skyworks Solutions, Inc."),
-    ("INCY", "Incyte Corporation"),
-    ("SPLK", "Splunk Inc."),
-    ("CPRT", "Copart, Inc."),
-    ("WDC", "Western Digital Corporation"),
-    ("NTAP", "NetApp, Inc."),
-    ("TTWO", "Take-Two Interactive Software, Inc."),
-    ("PEP", "PepsiCo, Inc."),
-    ("INTU", "Intuit Inc."),
-    ("TXN", "Texas Instruments Incorporated"),
-    ("BIIB", "Biogen Inc."),
-    ("ADSK", "Autodesk, Inc."),
-    ("MU", "Micron Technology, Inc."),
-    ("CMCSA", "Comcast Corporation"),
-];
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .map_err(io_error)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "json")
+            })
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let json = fs::read_to_string(&path).map_err(|source| IndexError::Io {
+                path: path.clone(),
+                source,
+            })?;
+            registry.insert(IndexDefinition::parse(&path, &json)?);
+        }
+
+        Ok(registry)
+    }
+
+    pub fn insert(&mut self, definition: IndexDefinition) {
+        self.indices.insert(definition.id.clone(), definition);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&IndexDefinition> {
+        self.indices.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &IndexDefinition> {
+        self.indices.values()
+    }
+
+    /**
+     * Name of the company behind the symbol, if it is a constituent of any index.
+     */
+    pub fn name_of(&self, symbol: &str) -> Option<&str> {
+        self.iter()
+            .flat_map(|definition| definition.constituents.iter())
+            .find(|constituent| constituent.symbol == symbol)
+            .map(|constituent| constituent.name.as_str())
+    }
+
+    pub fn ids(&self) -> Vec<&str> {
+        self.indices.keys().map(String::as_str).collect()
+    }
+}
@@ -0,0 +1,360 @@
+use axum::{
+    http::{header::CONTENT_TYPE, HeaderName, Method, StatusCode, Uri},
+    middleware,
+    response::Html,
+    routing::{delete, get, post},
+    Router,
+};
+use std::{path::Path, sync::Arc};
+use tower::ServiceBuilder;
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    cors::{Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
+
+use alphavantage_api::lib::AlphaVantageAPI;
+use cache::ResponseCache;
+use config::{Config, StartupError};
+use finnhub_api::lib::{FinnhubAPI, RATE_LIMIT_BURST, RATE_LIMIT_PER_MINUTE};
+use indices::IndexRegistry;
+use logging::REQUEST_ID_HEADER;
+use metrics::HttpMetrics;
+use provider::{MarketData, DATA_PROVIDER_HEADER};
+use quote_stream::QuoteStreams;
+use rate_limit::{DailyQuota, TokenBucket};
+use retry::RetryPolicy;
+use trades::TradesProxy;
+use watchlists::WatchlistStore;
+
+pub mod alphavantage_api;
+pub mod cache;
+pub mod candles;
+pub mod config;
+pub mod error;
+pub mod finnhub_api;
+mod handlers;
+pub mod health;
+pub mod http_client;
+mod indicators;
+pub mod indices;
+mod logging;
+mod metrics;
+pub mod provider;
+mod quote_stream;
+mod quotes;
+pub mod rate_limit;
+pub mod retry;
+mod single_flight;
+pub mod trades;
+pub mod watchlists;
+
+pub struct AppState {
+    finnhub: FinnhubAPI,
+    alphavantage: AlphaVantageAPI,
+    /**
+     * The same clients behind `MarketDataProvider`, used by the route handlers
+     */
+    market_data: MarketData,
+    cache: ResponseCache,
+    indices: IndexRegistry,
+    watchlists: WatchlistStore,
+    quote_streams: QuoteStreams,
+    trades: TradesProxy,
+    metrics: HttpMetrics,
+}
+
+impl AppState {
+    pub fn new(
+        finnhub: FinnhubAPI,
+        alphavantage: AlphaVantageAPI,
+        market_data: MarketData,
+        cache: ResponseCache,
+        indices: IndexRegistry,
+        watchlists: WatchlistStore,
+        trades: TradesProxy,
+    ) -> AppState {
+        AppState {
+            finnhub,
+            alphavantage,
+            market_data,
+            cache,
+            indices,
+            watchlists,
+            quote_streams: QuoteStreams::default(),
+            trades,
+            metrics: HttpMetrics::default(),
+        }
+    }
+
+    /**
+     * Sets up the API clients and stores from a validated config.
+     */
+    pub fn from_config(config: &Config) -> Result<AppState, StartupError> {
+        // One HTTP client (and connection pool) shared by all upstream API clients
+        let http_client = config.http_client.build()?;
+
+        // Upstream rate limits, defaults match the free plans
+        let mut finnhub = FinnhubAPI::new(&config.finnhub_api_token, http_client.clone());
+        let per_minute = config
+            .finnhub_calls_per_minute
+            .unwrap_or(RATE_LIMIT_PER_MINUTE);
+        finnhub.rate_limiter(TokenBucket::new(
+            per_minute.min(RATE_LIMIT_BURST),
+            per_minute,
+            config.finnhub_rate_limit_max_wait,
+        ));
+
+        let mut alphavantage = AlphaVantageAPI::new(&config.alphavantage_api_token, http_client);
+        if let Some(per_day) = config.alphavantage_calls_per_day {
+            alphavantage.quota(DailyQuota::new(per_day));
+        }
+
+        // Upstream URLs, e.g. to run against a mock server
+        if let Some(base_url) = &config.finnhub_base_url {
+            finnhub.base_url(base_url.clone());
+        }
+        if let Some(base_url) = &config.alphavantage_base_url {
+            alphavantage.base_url(base_url.clone());
+        }
+
+        // Retries of failed upstream calls, including the first attempt
+        if let Some(max_attempts) = config.upstream_max_attempts {
+            let retry_policy = || {
+                let mut retry_policy = RetryPolicy::default();
+                retry_policy.max_attempts = max_attempts;
+                retry_policy
+            };
+            finnhub.retry_policy(retry_policy());
+            alphavantage.retry_policy(retry_policy());
+        }
+
+        // Index constituents, the embedded defaults can be extended with a directory of JSON files
+        let indices = match &config.indices_dir {
+            Some(dir) => IndexRegistry::load_dir(Path::new(dir))?,
+            None => IndexRegistry::embedded(),
+        };
+
+        // Watchlists are persisted to a JSON file, which is created on the first change
+        let watchlists = WatchlistStore::load(config.watchlists_path.clone())?;
+
+        // Real-time trades, the URL can point to a mock server for testing. Unlike the REST API,
+        // the Finnhub WebSocket only accepts the token as query parameter, so the trades proxy
+        // must never log the URL unredacted.
+        let mut trades_url = config.finnhub_ws_url.clone();
+        trades_url
+            .query_pairs_mut()
+            .append_pair("token", &config.finnhub_api_token);
+
+        // Clones share the rate limits, retry stats and in-flight requests
+        let mut market_data = MarketData::new(vec![
+            Arc::new(finnhub.clone()),
+            Arc::new(alphavantage.clone()),
+        ]);
+        if let Some(priority) = &config.provider_priority {
+            market_data.priority(priority.clone());
+        }
+        for (operation, priority) in &config.operation_priorities {
+            market_data.operation_priority(*operation, priority.clone());
+        }
+
+        Ok(AppState::new(
+            finnhub,
+            alphavantage,
+            market_data,
+            ResponseCache::new(config.cache.clone()),
+            indices,
+            watchlists,
+            TradesProxy::spawn(trades_url.into()),
+        ))
+    }
+}
+
+async fn root() -> Html<&'static str> {
+    Html(
+        "<!DOCTYPE html>
+        <html>
+        <head>
+            <meta name='color-scheme' content='dark'></meta>
+            <title>Giga Stonks API</title>
+            <style>
+            body {
+                font-family: Georgia, sans-serif;
+            }
+            </style>
+        </head>
+        <body>
+            <h1>Giga Stonks API</h1>
+        </body>
+        </html>",
+    )
+}
+
+async fn fallback(uri: Uri) -> (StatusCode, String) {
+    (StatusCode::NOT_FOUND, format!("No route for {}", uri))
+}
+
+/**
+ * All routes of the API with their middleware.
+ */
+pub fn router(app_state: Arc<AppState>) -> Router {
+    // CORS setup via tower service in middleware layer
+    let cors = CorsLayer::new()
+        .allow_methods(vec![
+            Method::GET,
+            Method::POST,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([CONTENT_TYPE, HeaderName::from_static(REQUEST_ID_HEADER)])
+        .expose_headers([
+            HeaderName::from_static(DATA_PROVIDER_HEADER),
+            HeaderName::from_static(REQUEST_ID_HEADER),
+        ])
+        .allow_origin(Any)
+        .allow_credentials(false);
+
+    let service = ServiceBuilder::new().layer(cors);
+
+    // Routes setup
+    let api_routes_v1 = Router::new()
+        .route("/market-news", get(handlers::finnhub::get_market_news))
+        .route(
+            // /api/v1/company-news?symbol=XXXX&time_from=yyyy-mm-dd&time_to=yyyy-mm-dd
+            "/company-news",
+            get(handlers::finnhub::get_company_news),
+        )
+        .route(
+            "/market-status",
+            get(handlers::alphavantage::get_market_status),
+        )
+        .route(
+            // /api/v1/news-sentiment?time_from=yyyymmdd
+            "/news-sentiment",
+            get(handlers::alphavantage::get_news_sentiment),
+        )
+        .route(
+            // /api/v1/news-sentiment-ticker?ticker=XXXX?time_from=yyyymmdd
+            "/news-sentiment-ticker",
+            get(handlers::alphavantage::get_news_sentiment_ticker),
+        )
+        .route(
+            // /api/v1/social-sentiment?symbol=XXXX&time_from=yyyy-mm-dd
+            "/social-sentiment",
+            get(handlers::finnhub::get_social_sentiment),
+        )
+        .route("/indices", get(handlers::indices::get_indices))
+        .route(
+            // Server-Sent Events, see handlers::stream
+            "/stream/quotes/:index",
+            get(handlers::stream::stream_quotes_for_index),
+        )
+        .route(
+            // WebSocket, see handlers::trades
+            "/stream/trades",
+            get(handlers::trades::get_trades_socket),
+        )
+        .route(
+            // /api/v1/quotes?symbols=AAPL,TSLA,SAP
+            "/quotes",
+            get(handlers::finnhub::get_quotes_for_symbols),
+        )
+        .route(
+            "/quotes/:index",
+            get(handlers::finnhub::get_quotes_for_index),
+        )
+        .route(
+            // /api/v1/quote/AAPL?include=profile
+            "/quote/:symbol",
+            get(handlers::finnhub::get_quote_for_symbol),
+        )
+        .route(
+            // /api/v1/candles/AAPL?resolution=D&from=yyyy-mm-dd&to=yyyy-mm-dd
+            "/candles/:symbol",
+            get(handlers::candles::get_candles),
+        )
+        .route(
+            // /api/v1/indicators/AAPL?set=rsi14,sma50,macd&days=100
+            "/indicators/:symbol",
+            get(handlers::indicators::get_indicators),
+        )
+        .route(
+            "/company-profile/:symbol",
+            get(handlers::finnhub::get_company_profile),
+        )
+        .route(
+            "/earnings-calendar",
+            get(handlers::alphavantage::get_earnings_calendar),
+        )
+        .route("/status", get(handlers::status::get_status))
+        .route(
+            "/watchlists",
+            get(handlers::watchlists::get_watchlists).post(handlers::watchlists::create_watchlist),
+        )
+        .route(
+            // PATCH renames the watchlist, body { "name": "..." }
+            "/watchlists/:id",
+            get(handlers::watchlists::get_watchlist)
+                .patch(handlers::watchlists::rename_watchlist)
+                .delete(handlers::watchlists::delete_watchlist),
+        )
+        .route(
+            // body { "symbols": ["AAPL", "SAP"] }
+            "/watchlists/:id/symbols",
+            post(handlers::watchlists::add_watchlist_symbols),
+        )
+        .route(
+            "/watchlists/:id/symbols/:symbol",
+            delete(handlers::watchlists::remove_watchlist_symbol),
+        )
+        .route(
+            "/quotes/watchlist/:id",
+            get(handlers::watchlists::get_quotes_for_watchlist),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            cache::cache_responses,
+        ))
+        // Outside of the cache, so cached responses are counted too
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            metrics::track_requests,
+        ))
+        // Added after the routes, so the CORS headers apply to all of them
+        .layer(service);
+
+    // App setup
+    Router::new()
+        .route("/", get(root))
+        // Probes for load balancers, outside of /api/v1 and its cache
+        .route("/healthz", get(handlers::status::get_healthz))
+        .route("/readyz", get(handlers::status::get_readyz))
+        .route("/metrics", get(handlers::status::get_metrics))
+        // Only wraps the routes above, the nested ones have their own
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            metrics::track_requests,
+        ))
+        .nest("/api/v1", api_routes_v1)
+        .fallback(fallback)
+        // Keeps the X-Request-Id of the client or generates one, and returns it in the response
+        .layer(
+            ServiceBuilder::new()
+                .layer(SetRequestIdLayer::new(
+                    HeaderName::from_static(REQUEST_ID_HEADER),
+                    MakeRequestUuid,
+                ))
+                .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+                    REQUEST_ID_HEADER,
+                )))
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(logging::request_span)
+                        .on_response(DefaultOnResponse::new().level(Level::INFO)),
+                )
+                .layer(CatchPanicLayer::custom(logging::handle_panic)),
+        )
+        .with_state(app_state)
+}
@@ -0,0 +1,157 @@
+use std::{any::Any, future::Future, time::Instant};
+
+use axum::{
+    http::{Request, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use tracing::{field, info_span, Instrument, Span};
+
+use crate::{error::Provider, health::ErrorKind};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/**
+ * Query parameters carrying the API tokens
+ */
+const SECRET_PARAMS: [&str; 2] = ["token=", "apikey="];
+
+/**
+ * Query parameters naming the requested symbols
+ */
+const SYMBOL_PARAMS: [&str; 2] = ["symbol", "tickers"];
+
+/**
+ * Replaces the values of the API token query parameters in `text`, e.g. an upstream URL or an
+ * error message containing one.
+ */
+pub fn redact(text: &str) -> String {
+    const REDACTED: &str = "REDACTED";
+
+    let mut redacted = text.to_string();
+    for param in SECRET_PARAMS {
+        let mut from = 0;
+        while let Some(position) = redacted[from..].find(param) {
+            let start = from + position + param.len();
+            let end = redacted[start..]
+                .find(|c: char| c == '&' || c == ')' || c.is_whitespace())
+                .map_or(redacted.len(), |len| start + len);
+            redacted.replace_range(start..end, REDACTED);
+            from = start + REDACTED.len();
+        }
+    }
+
+    redacted
+}
+
+/**
+ * Span of an incoming request, every log line of the request carries its ID.
+ */
+pub fn request_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id,
+    )
+}
+
+/**
+ * Runs an upstream call in a span with the provider, endpoint, symbol and redacted URL, and
+ * logs its outcome and duration. The HTTP status is added by the call with `record_status`.
+ */
+pub async fn upstream_call<T, E: ErrorKind>(
+    provider: Provider,
+    endpoint: &'static str,
+    url: &str,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let symbol = url::Url::parse(url).ok().and_then(|url| {
+        url.query_pairs()
+            .find(|(key, _)| SYMBOL_PARAMS.contains(&key.as_ref()))
+            .map(|(_, value)| value.into_owned())
+    });
+    let span = info_span!(
+        "upstream",
+        provider = provider.as_str(),
+        endpoint,
+        symbol,
+        url = %redact(url),
+        status = field::Empty,
+        duration_ms = field::Empty,
+    );
+
+    let start = Instant::now();
+    let result = call.instrument(span.clone()).await;
+    span.record("duration_ms", start.elapsed().as_millis() as u64);
+
+    match &result {
+        Ok(_) => tracing::info!(parent: &span, "Upstream call succeeded"),
+        Err(err) => tracing::warn!(
+            parent: &span,
+            kind = err.kind(),
+            error = %redact(&err.to_string()),
+            "Upstream call failed",
+        ),
+    }
+
+    result
+}
+
+/**
+ * Adds the HTTP status of an upstream response to the span of the call.
+ */
+pub fn record_status(status: StatusCode) {
+    Span::current().record("status", status.as_u16());
+}
+
+/**
+ * Logs the message of a panicked handler and answers with an internal error.
+ */
+pub fn handle_panic(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    tracing::error!(panic = message, "Request handler panicked");
+
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({
+            "code": "internal_error",
+            "message": "Internal server error",
+            "provider": null,
+        })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_api_tokens() {
+        assert_eq!(
+            redact("https://finnhub.io/api/v1/quote?symbol=AAPL&token=secret"),
+            "https://finnhub.io/api/v1/quote?symbol=AAPL&token=REDACTED"
+        );
+        assert_eq!(
+            redact("error sending request for url (https://www.alphavantage.co/query?apikey=secret&function=NEWS_SENTIMENT): timed out"),
+            "error sending request for url (https://www.alphavantage.co/query?apikey=REDACTED&function=NEWS_SENTIMENT): timed out"
+        );
+        assert_eq!(
+            redact("?token=a&x=1&token=b"),
+            "?token=REDACTED&x=1&token=REDACTED"
+        );
+        assert_eq!(redact("no secrets"), "no secrets");
+    }
+}
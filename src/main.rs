@@ -1,121 +1,55 @@
-use axum::{
-    http::{Method, StatusCode, Uri},
-    response::Html,
-    routing::get,
-    Router,
-};
-use shuttle_secrets::SecretStore;
+use giga_stonks_api::{config::Config, router, AppState};
 use std::sync::Arc;
-use tower::ServiceBuilder;
-use tower_http::cors::{Any, CorsLayer};
-
-pub mod alphavantage_api;
-mod finnhub_api;
-mod handlers;
-pub mod indices;
-
-pub struct AppState {
-    api_token_finnhub: String,
-    api_token_alphavantage: String,
-}
-
-async fn root() -> Html<&'static str> {
-    Html(
-        "<!DOCTYPE html>
-        <html>
-        <head>
-            <meta name='color-scheme' content='dark'></meta>
-            <title>Giga Stonks API</title>
-            <style>
-            body {
-                font-family: Georgia, sans-serif;
-            }
-            </style>
-        </head>
-        <body>
-            <h1>Giga Stonks API</h1>
-        </body>
-        </html>",
-    )
-}
-
-async fn fallback(uri: Uri) -> (StatusCode, String) {
-    (StatusCode::NOT_FOUND, format!("No route for {}", uri))
-}
 
+#[cfg(feature = "shuttle")]
 #[shuttle_runtime::main]
-async fn axum(#[shuttle_secrets::Secrets] secret_store: SecretStore) -> shuttle_axum::ShuttleAxum {
-    let api_token_finnhub = if let Some(secret) = secret_store.get("FINNHUB_API_TOKEN") {
-        secret
-    } else {
-        "Finnhub Api Token Not Set".to_string()
-    };
-    let api_token_alphavantage = if let Some(secret) = secret_store.get("ALPHA_VANTAGE_API_TOKEN") {
-        secret
-    } else {
-        "Alpha Vantage Api Token Not Set".to_string()
-    };
-
-    let app_state = Arc::new(AppState {
-        api_token_finnhub,
-        api_token_alphavantage,
-    });
-
-    // CORS setup via tower service in middleware layer
-    let cors = CorsLayer::new()
-        .allow_methods(vec![Method::GET])
-        .allow_origin(Any)
-        .allow_credentials(false);
-
-    let service = ServiceBuilder::new().layer(cors);
+async fn axum(
+    #[shuttle_secrets::Secrets] secret_store: shuttle_secrets::SecretStore,
+) -> shuttle_axum::ShuttleAxum {
+    // Same keys as the env vars of the standalone server
+    let config = Config::from_lookup(|key| secret_store.get(key))
+        .map_err(|err| shuttle_runtime::Error::Custom(err.into()))?;
+    let app_state =
+        AppState::from_config(&config).map_err(|err| shuttle_runtime::Error::Custom(err.into()))?;
+
+    Ok(router(Arc::new(app_state)).into())
+}
 
-    // Routes setup
-    let api_routes_v1 = Router::new()
-        .layer(service)
-        .route("/market-news", get(handlers::finnhub::get_market_news))
-        .route(
-            // /api/v1/company-news?symbol=XXXX&time_from=yyyy-mm-dd&time_to=yyyy-mm-dd
-            "/company-news",
-            get(handlers::finnhub::get_company_news),
-        )
-        .route(
-            "/market-status",
-            get(handlers::alphavantage::get_market_status),
-        )
-        .route(
-            // /api/v1/news-sentiment?time_from=yyyymmdd
-            "/news-sentiment",
-            get(handlers::alphavantage::get_news_sentiment),
-        )
-        .route(
-            // /api/v1/news-sentiment-ticker?ticker=XXXX?time_from=yyyymmdd
-            "/news-sentiment-ticker",
-            get(handlers::alphavantage::get_news_sentiment_ticker),
-        )
-        .route(
-            // /api/v1/social-sentiment?symbol=XXXX&time_from=yyyy-mm-dd
-            "/social-sentiment",
-            get(handlers::finnhub::get_social_sentiment),
+#[cfg(not(feature = "shuttle"))]
+#[tokio::main]
+async fn main() -> std::process::ExitCode {
+    // Log level from RUST_LOG, e.g. RUST_LOG=giga_stonks_api=debug, Shuttle sets this up itself
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
         )
-        .route(
-            "/quotes/:index",
-            get(handlers::finnhub::get_quotes_for_index),
-        )
-        .route(
-            "/company-profile/:symbol",
-            get(handlers::finnhub::get_company_profile),
-        )
-        .route(
-            "/earnings-calendar",
-            get(handlers::alphavantage::get_earnings_calendar),
-        );
-
-    // App setup
-    let app = Router::new()
-        .route("/", get(root))
-        .nest("/api/v1", api_routes_v1)
-        .fallback(fallback)
-        .with_state(app_state);
+        .init();
+
+    match serve().await {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(err) => {
+            tracing::error!(error = %err, "Failed to start the server");
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
 
-    Ok(app.into())
+/**
+ * Runs the standalone server until Ctrl+C.
+ */
+#[cfg(not(feature = "shuttle"))]
+async fn serve() -> Result<(), giga_stonks_api::config::StartupError> {
+    let config = Config::load()?;
+    let app_state = AppState::from_config(&config)?;
+
+    tracing::info!(addr = %config.listen_addr, "Listening");
+    axum::Server::try_bind(&config.listen_addr)?
+        .serve(router(Arc::new(app_state)).into_make_service())
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
+    Ok(())
 }
@@ -0,0 +1,339 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use axum::{
+    extract::{MatchedPath, State},
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{error::Provider, health::ProviderHealthSnapshot, retry::RetryStatsSnapshot, AppState};
+
+/**
+ * Upper bounds of the request duration buckets in seconds
+ */
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Default)]
+struct RouteMetrics {
+    /**
+     * Responses by status code
+     */
+    responses: BTreeMap<u16, u64>,
+    /**
+     * Requests per duration bucket (not cumulative), the last one is `+Inf`
+     */
+    buckets: [u64; DURATION_BUCKETS.len() + 1],
+    duration_sum: f64,
+}
+
+/**
+ * Request counts, status codes and durations per route and method.
+ */
+#[derive(Debug, Default)]
+pub struct HttpMetrics {
+    /**
+     * Keyed by route pattern (e.g. "/api/v1/quote/:symbol") and method
+     */
+    routes: Mutex<BTreeMap<(String, String), RouteMetrics>>,
+}
+
+impl HttpMetrics {
+    fn observe(&self, route: &str, method: &str, status: u16, seconds: f64) {
+        let mut routes = self.routes.lock().unwrap();
+        let metrics = routes
+            .entry((route.to_string(), method.to_string()))
+            .or_default();
+
+        *metrics.responses.entry(status).or_default() += 1;
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        metrics.buckets[bucket] += 1;
+        metrics.duration_sum += seconds;
+    }
+
+    fn write(&self, out: &mut Exposition) {
+        let routes = self.routes.lock().unwrap();
+
+        out.header(
+            "http_requests_total",
+            "counter",
+            "Requests by route, method and status code.",
+        );
+        for ((route, method), metrics) in routes.iter() {
+            for (status, count) in &metrics.responses {
+                let status = status.to_string();
+                out.sample(
+                    "http_requests_total",
+                    &[("route", route), ("method", method), ("status", &status)],
+                    count,
+                );
+            }
+        }
+
+        out.header(
+            "http_request_duration_seconds",
+            "histogram",
+            "Request durations by route and method.",
+        );
+        for ((route, method), metrics) in routes.iter() {
+            let labels = [("route", route.as_str()), ("method", method.as_str())];
+            let mut cumulative = 0;
+            for (i, count) in metrics.buckets.iter().enumerate() {
+                cumulative += count;
+                let bound = DURATION_BUCKETS
+                    .get(i)
+                    .map_or("+Inf".to_string(), ToString::to_string);
+                out.sample(
+                    "http_request_duration_seconds_bucket",
+                    &[labels[0], labels[1], ("le", &bound)],
+                    cumulative,
+                );
+            }
+            out.sample(
+                "http_request_duration_seconds_sum",
+                &labels,
+                metrics.duration_sum,
+            );
+            out.sample("http_request_duration_seconds_count", &labels, cumulative);
+        }
+    }
+}
+
+/**
+ * Middleware recording every request in the `HttpMetrics` of the `AppState`. Has to be added
+ * with `route_layer`, the route pattern isn't known before routing.
+ */
+pub async fn track_requests<B>(
+    State(state): State<Arc<AppState>>,
+    matched_path: Option<MatchedPath>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let route = matched_path
+        .as_ref()
+        .map_or("unknown", |path| path.as_str())
+        .to_string();
+    let method = req.method().to_string();
+    let start = Instant::now();
+
+    let response = next.run(req).await;
+
+    state.metrics.observe(
+        &route,
+        &method,
+        response.status().as_u16(),
+        start.elapsed().as_secs_f64(),
+    );
+    response
+}
+
+/**
+ * All metrics in the Prometheus text format.
+ */
+pub fn render(state: &AppState) -> String {
+    let mut out = Exposition::default();
+    state.metrics.write(&mut out);
+
+    let providers: [(Provider, RetryStatsSnapshot, ProviderHealthSnapshot); 2] = [
+        (
+            Provider::Finnhub,
+            state.finnhub.retry_stats(),
+            state.finnhub.health(),
+        ),
+        (
+            Provider::AlphaVantage,
+            state.alphavantage.retry_stats(),
+            state.alphavantage.health(),
+        ),
+    ];
+
+    out.header(
+        "upstream_requests_total",
+        "counter",
+        "Upstream calls by provider, not counting retries.",
+    );
+    for (provider, stats, _) in &providers {
+        out.sample(
+            "upstream_requests_total",
+            &[("provider", provider.as_str())],
+            stats.requests,
+        );
+    }
+
+    out.header(
+        "upstream_retries_total",
+        "counter",
+        "Retried upstream calls by provider.",
+    );
+    for (provider, stats, _) in &providers {
+        out.sample(
+            "upstream_retries_total",
+            &[("provider", provider.as_str())],
+            stats.retries,
+        );
+    }
+
+    out.header(
+        "upstream_retries_exhausted_total",
+        "counter",
+        "Upstream calls by provider which still failed after the last attempt.",
+    );
+    for (provider, stats, _) in &providers {
+        out.sample(
+            "upstream_retries_exhausted_total",
+            &[("provider", provider.as_str())],
+            stats.exhausted,
+        );
+    }
+
+    out.header(
+        "upstream_errors_total",
+        "counter",
+        "Failed upstream calls by provider and error.",
+    );
+    for (provider, _, health) in &providers {
+        for (kind, count) in &health.errors {
+            out.sample(
+                "upstream_errors_total",
+                &[("provider", provider.as_str()), ("error", kind)],
+                count,
+            );
+        }
+    }
+
+    let cache = state.cache.stats();
+    out.header(
+        "cache_hits_total",
+        "counter",
+        "Requests served from the response cache.",
+    );
+    out.sample("cache_hits_total", &[], cache.hits);
+    out.header(
+        "cache_misses_total",
+        "counter",
+        "Cacheable requests not found in the response cache.",
+    );
+    out.sample("cache_misses_total", &[], cache.misses);
+    out.header(
+        "cache_hit_ratio",
+        "gauge",
+        "Share of cacheable requests served from the cache.",
+    );
+    let lookups = cache.hits + cache.misses;
+    let ratio = if lookups == 0 {
+        0.0
+    } else {
+        cache.hits as f64 / lookups as f64
+    };
+    out.sample("cache_hit_ratio", &[], ratio);
+
+    // Unknown until the first Finnhub response
+    let remaining = state
+        .finnhub
+        .rate_limit_info()
+        .and_then(|info| info.ratelimit_remaining.parse::<u32>().ok());
+    if let Some(remaining) = remaining {
+        out.header(
+            "finnhub_ratelimit_remaining",
+            "gauge",
+            "X-Ratelimit-Remaining of the latest Finnhub response.",
+        );
+        out.sample("finnhub_ratelimit_remaining", &[], remaining);
+    }
+
+    let quota = state.alphavantage.quota_usage();
+    out.header(
+        "alphavantage_daily_quota_used",
+        "gauge",
+        "Alpha Vantage calls used today.",
+    );
+    out.sample("alphavantage_daily_quota_used", &[], quota.used);
+    out.header(
+        "alphavantage_daily_quota_limit",
+        "gauge",
+        "Alpha Vantage calls allowed per day.",
+    );
+    out.sample("alphavantage_daily_quota_limit", &[], quota.limit);
+
+    out.text
+}
+
+#[derive(Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        self.text
+            .push_str(&format!("# HELP {name} {help}\n# TYPE {name} {kind}\n"));
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_label(value)))
+                .collect();
+            self.text.push_str(&format!("{{{}}}", labels.join(",")));
+        }
+        self.text.push_str(&format!(" {value}\n"));
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_requests_and_cumulative_buckets() {
+        let metrics = HttpMetrics::default();
+        metrics.observe("/api/v1/quote/:symbol", "GET", 200, 0.004);
+        metrics.observe("/api/v1/quote/:symbol", "GET", 200, 0.3);
+        metrics.observe("/api/v1/quote/:symbol", "GET", 429, 12.0);
+
+        let mut out = Exposition::default();
+        metrics.write(&mut out);
+        let text = out.text;
+
+        let labels = r#"route="/api/v1/quote/:symbol",method="GET""#;
+        for line in [
+            format!(r#"http_requests_total{{{labels},status="200"}} 2"#),
+            format!(r#"http_requests_total{{{labels},status="429"}} 1"#),
+            format!(r#"http_request_duration_seconds_bucket{{{labels},le="0.005"}} 1"#),
+            format!(r#"http_request_duration_seconds_bucket{{{labels},le="0.25"}} 1"#),
+            format!(r#"http_request_duration_seconds_bucket{{{labels},le="0.5"}} 2"#),
+            format!(r#"http_request_duration_seconds_bucket{{{labels},le="10"}} 2"#),
+            format!(r#"http_request_duration_seconds_bucket{{{labels},le="+Inf"}} 3"#),
+            format!(r#"http_request_duration_seconds_count{{{labels}}} 3"#),
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line} in\n{text}");
+        }
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let mut out = Exposition::default();
+        out.sample("metric", &[("label", "a\"b\\c\nd")], 1);
+
+        assert_eq!(out.text, "metric{label=\"a\\\"b\\\\c\\nd\"} 1\n");
+    }
+}
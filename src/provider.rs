@@ -0,0 +1,652 @@
+use std::{collections::HashMap, convert::Infallible, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use axum::{
+    http::HeaderValue,
+    response::{IntoResponseParts, ResponseParts},
+};
+use futures_util::future::{join_all, BoxFuture};
+
+use crate::{
+    alphavantage_api::{
+        earnings_calendar::Earning, market_status::MarketStatusInfo,
+        news_sentiment::NewsSentimentFeedEntry,
+    },
+    candles::{Candle, CandleRange},
+    error::{ApiError, Provider},
+    finnhub_api::{
+        company_profile::CompanyProfile,
+        market_news::ArticleMarketNews,
+        social_sentiment::SocialSentimentResponse,
+        symbol_quote::{MarketQuotes, SymbolQuoteExtended, SymbolQuoteFailure},
+    },
+    indices::Constituent,
+};
+
+/**
+ * Response header naming the providers which served the data.
+ */
+pub const DATA_PROVIDER_HEADER: &str = "x-data-provider";
+
+/**
+ * Symbols of a quote batch which may fall back to the next provider. Alpha Vantage only
+ * allows 25 calls per day, a rate limited index would use them up at once otherwise.
+ */
+const MAX_BATCH_FALLBACK: usize = 5;
+
+/**
+ * The operations a market data provider may support.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Quote,
+    MarketNews,
+    CompanyNews,
+    CompanyProfile,
+    NewsSentiment,
+    SocialSentiment,
+    MarketStatus,
+    EarningsCalendar,
+    Candles,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 9] = [
+        Self::Quote,
+        Self::MarketNews,
+        Self::CompanyNews,
+        Self::CompanyProfile,
+        Self::NewsSentiment,
+        Self::SocialSentiment,
+        Self::MarketStatus,
+        Self::EarningsCalendar,
+        Self::Candles,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Quote => "quote",
+            Self::MarketNews => "market_news",
+            Self::CompanyNews => "company_news",
+            Self::CompanyProfile => "company_profile",
+            Self::NewsSentiment => "news_sentiment",
+            Self::SocialSentiment => "social_sentiment",
+            Self::MarketStatus => "market_status",
+            Self::EarningsCalendar => "earnings_calendar",
+            Self::Candles => "candles",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/**
+ * An upstream source of market data. Every operation defaults to `ApiError::Unsupported`,
+ * implementations override the ones listed in `operations`.
+ */
+#[async_trait]
+pub trait MarketDataProvider: Send + Sync {
+    fn provider(&self) -> Provider;
+
+    fn operations(&self) -> &'static [Operation];
+
+    fn supports(&self, operation: Operation) -> bool {
+        self.operations().contains(&operation)
+    }
+
+    /**
+     * `name` is only passed through to the quote.
+     */
+    async fn quote(&self, _symbol: &str, _name: &str) -> Result<SymbolQuoteExtended, ApiError> {
+        Err(ApiError::Unsupported(Operation::Quote))
+    }
+
+    /**
+     * Quotes of all symbols of a market, the symbols which could not be fetched end up in
+     * `MarketQuotes::failures`. Fetches the symbols concurrently by default.
+     */
+    async fn quotes(&self, market: &[Constituent]) -> MarketQuotes {
+        let results = join_all(
+            market
+                .iter()
+                .map(|constituent| self.quote(&constituent.symbol, &constituent.name)),
+        )
+        .await;
+
+        let mut market_quotes = MarketQuotes::default();
+        for (constituent, result) in market.iter().zip(results) {
+            match result {
+                Ok(quote) => market_quotes.quotes.push(quote),
+                Err(error) => market_quotes.failures.push(SymbolQuoteFailure {
+                    symbol: constituent.symbol.clone(),
+                    name: constituent.name.clone(),
+                    error,
+                }),
+            }
+        }
+
+        market_quotes
+    }
+
+    async fn market_news(&self) -> Result<Vec<ArticleMarketNews>, ApiError> {
+        Err(ApiError::Unsupported(Operation::MarketNews))
+    }
+
+    /**
+     * `time_from` and `time_to` are yyyy-mm-dd dates.
+     */
+    async fn company_news(
+        &self,
+        _symbol: &str,
+        _time_from: &str,
+        _time_to: &str,
+    ) -> Result<Vec<ArticleMarketNews>, ApiError> {
+        Err(ApiError::Unsupported(Operation::CompanyNews))
+    }
+
+    async fn company_profile(&self, _symbol: &str) -> Result<CompanyProfile, ApiError> {
+        Err(ApiError::Unsupported(Operation::CompanyProfile))
+    }
+
+    /**
+     * News of all tickers without a `ticker`, `time_from` is a yyyymmdd date.
+     */
+    async fn news_sentiment(
+        &self,
+        _ticker: Option<&str>,
+        _time_from: &str,
+    ) -> Result<Vec<NewsSentimentFeedEntry>, ApiError> {
+        Err(ApiError::Unsupported(Operation::NewsSentiment))
+    }
+
+    /**
+     * `time_from` is a yyyy-mm-dd date.
+     */
+    async fn social_sentiment(
+        &self,
+        _symbol: &str,
+        _time_from: &str,
+    ) -> Result<SocialSentimentResponse, ApiError> {
+        Err(ApiError::Unsupported(Operation::SocialSentiment))
+    }
+
+    async fn market_status(&self) -> Result<Vec<MarketStatusInfo>, ApiError> {
+        Err(ApiError::Unsupported(Operation::MarketStatus))
+    }
+
+    async fn earnings_calendar(&self) -> Result<Vec<Earning>, ApiError> {
+        Err(ApiError::Unsupported(Operation::EarningsCalendar))
+    }
+
+    async fn candles(&self, _symbol: &str, _range: &CandleRange) -> Result<Vec<Candle>, ApiError> {
+        Err(ApiError::Unsupported(Operation::Candles))
+    }
+}
+
+/**
+ * The configured providers with the order they are tried in, by default and per operation.
+ * Handlers go through `serve`, which falls back to the next provider if one is unavailable.
+ */
+#[derive(Clone, Default)]
+pub struct MarketData {
+    providers: Vec<Arc<dyn MarketDataProvider>>,
+    priority: Vec<Provider>,
+    operation_priorities: HashMap<Operation, Vec<Provider>>,
+    max_batch_fallback: usize,
+}
+
+impl MarketData {
+    /**
+     * Tries the providers in the given order until overridden with `priority`.
+     */
+    pub fn new(providers: Vec<Arc<dyn MarketDataProvider>>) -> MarketData {
+        let priority = providers
+            .iter()
+            .map(|provider| provider.provider())
+            .collect();
+        MarketData {
+            providers,
+            priority,
+            operation_priorities: HashMap::new(),
+            max_batch_fallback: MAX_BATCH_FALLBACK,
+        }
+    }
+
+    /**
+     * Order of the providers for all operations, unlisted providers are not used.
+     */
+    pub fn priority(&mut self, priority: Vec<Provider>) -> &mut MarketData {
+        self.priority = priority;
+        self
+    }
+
+    /**
+     * Order of the providers for a single operation, overrides `priority`.
+     */
+    pub fn operation_priority(
+        &mut self,
+        operation: Operation,
+        priority: Vec<Provider>,
+    ) -> &mut MarketData {
+        self.operation_priorities.insert(operation, priority);
+        self
+    }
+
+    /**
+     * Number of symbols of a quote batch which may fall back to the next provider, the
+     * others are reported as failed.
+     */
+    pub fn max_batch_fallback(&mut self, max_batch_fallback: usize) -> &mut MarketData {
+        self.max_batch_fallback = max_batch_fallback;
+        self
+    }
+
+    /**
+     * All providers of the operation, the preferred one first.
+     */
+    pub fn providers_for(&self, operation: Operation) -> Vec<&dyn MarketDataProvider> {
+        self.operation_priorities
+            .get(&operation)
+            .unwrap_or(&self.priority)
+            .iter()
+            .filter_map(|provider| {
+                self.providers
+                    .iter()
+                    .find(|candidate| candidate.provider() == *provider)
+            })
+            .map(|provider| provider.as_ref())
+            .filter(|provider| provider.supports(operation))
+            .collect()
+    }
+
+    /**
+     * Runs `call` with the providers of the operation in order until one succeeds. Falls back
+     * to the next provider only if the provider itself failed, e.g. because it is rate
+     * limited. Returns the provider which served the data.
+     */
+    pub async fn serve<'a, T>(
+        &'a self,
+        operation: Operation,
+        call: impl Fn(&'a dyn MarketDataProvider) -> BoxFuture<'a, Result<T, ApiError>>,
+    ) -> Result<(Provider, T), ApiError> {
+        let mut last_error = ApiError::Unsupported(operation);
+
+        for provider in self.providers_for(operation) {
+            match call(provider).await {
+                Ok(data) => return Ok((provider.provider(), data)),
+                Err(err) if err.is_provider_unavailable() => {
+                    tracing::info!(
+                        provider = provider.provider().as_str(),
+                        %operation,
+                        code = err.code(),
+                        "Provider unavailable, trying the next one"
+                    );
+                    last_error = err;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /**
+     * Quotes of all symbols of a market. Up to `max_batch_fallback` symbols whose provider was
+     * unavailable are fetched from the next provider, every quote names the provider which
+     * served it.
+     */
+    pub async fn quotes(&self, market: &[Constituent]) -> Result<MarketQuotes, ApiError> {
+        self.quotes_with_fallback(market, self.max_batch_fallback)
+            .await
+    }
+
+    /**
+     * Quotes of all symbols of a market from the preferred provider only. For repeated polls,
+     * which would use up the quota of the next provider with every poll.
+     */
+    pub async fn quotes_without_fallback(
+        &self,
+        market: &[Constituent],
+    ) -> Result<MarketQuotes, ApiError> {
+        self.quotes_with_fallback(market, 0).await
+    }
+
+    async fn quotes_with_fallback(
+        &self,
+        market: &[Constituent],
+        max_fallback: usize,
+    ) -> Result<MarketQuotes, ApiError> {
+        let providers = self.providers_for(Operation::Quote);
+        if providers.is_empty() {
+            return Err(ApiError::Unsupported(Operation::Quote));
+        }
+
+        let mut market_quotes = MarketQuotes::default();
+        let mut remaining = market.to_vec();
+
+        for (i, provider) in providers.iter().enumerate() {
+            let has_fallback = i + 1 < providers.len();
+            let batch = provider.quotes(&remaining).await;
+            market_quotes.quotes.extend(batch.quotes);
+
+            remaining = Vec::new();
+            for failure in batch.failures {
+                if has_fallback
+                    && remaining.len() < max_fallback
+                    && failure.error.is_provider_unavailable()
+                {
+                    remaining.push(Constituent {
+                        symbol: failure.symbol,
+                        name: failure.name,
+                    });
+                } else {
+                    market_quotes.failures.push(failure);
+                }
+            }
+
+            if remaining.is_empty() {
+                break;
+            }
+        }
+
+        Ok(market_quotes)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Unknown data provider '{0}', use 'finnhub' or 'alphavantage'")]
+pub struct UnknownProvider(String);
+
+/**
+ * Parses a comma separated priority list, e.g. "alphavantage,finnhub".
+ */
+pub fn parse_priority(priority: &str) -> Result<Vec<Provider>, UnknownProvider> {
+    let mut providers = Vec::new();
+
+    for name in priority.split(',').filter(|name| !name.trim().is_empty()) {
+        let provider = Provider::parse(name).ok_or_else(|| UnknownProvider(name.to_string()))?;
+        if !providers.contains(&provider) {
+            providers.push(provider);
+        }
+    }
+
+    Ok(providers)
+}
+
+/**
+ * `X-Data-Provider` header naming the providers which served a response, e.g.
+ * "finnhub" or "finnhub, alphavantage" if quotes came from both.
+ */
+pub struct ServedBy(pub Vec<Provider>);
+
+impl ServedBy {
+    pub fn quotes(market_quotes: &MarketQuotes) -> ServedBy {
+        ServedBy(
+            market_quotes
+                .quotes
+                .iter()
+                .map(|quote| quote.provider)
+                .collect(),
+        )
+    }
+}
+
+impl From<Provider> for ServedBy {
+    fn from(provider: Provider) -> Self {
+        ServedBy(vec![provider])
+    }
+}
+
+impl IntoResponseParts for ServedBy {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let mut providers: Vec<&str> = Vec::new();
+        for provider in self.0 {
+            if !providers.contains(&provider.as_str()) {
+                providers.push(provider.as_str());
+            }
+        }
+
+        if !providers.is_empty() {
+            res.headers_mut().insert(
+                DATA_PROVIDER_HEADER,
+                HeaderValue::from_str(&providers.join(", ")).unwrap(),
+            );
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finnhub_api::lib::FinnhubError;
+
+    struct MockProvider {
+        provider: Provider,
+        operations: &'static [Operation],
+        /**
+         * Symbols the provider is rate limited for
+         */
+        rate_limited: &'static [&'static str],
+    }
+
+    #[async_trait]
+    impl MarketDataProvider for MockProvider {
+        fn provider(&self) -> Provider {
+            self.provider
+        }
+
+        fn operations(&self) -> &'static [Operation] {
+            self.operations
+        }
+
+        async fn quote(&self, symbol: &str, name: &str) -> Result<SymbolQuoteExtended, ApiError> {
+            if self.rate_limited.contains(&symbol) {
+                return Err(FinnhubError::RateLimited { retry_after: None }.into());
+            }
+            if symbol == "UNKNOWN" {
+                return Err(FinnhubError::SymbolNotFound {
+                    symbol: symbol.to_string(),
+                }
+                .into());
+            }
+
+            Ok(SymbolQuoteExtended {
+                current_price: 10.0,
+                delta: 1.0,
+                delta_percent: 11.1,
+                high: 10.5,
+                low: 9.0,
+                open: 9.0,
+                previous_close: 9.0,
+                timestamp: 1,
+                symbol: symbol.to_string(),
+                name: name.to_string(),
+                provider: self.provider,
+                rate_limit_info: None,
+            })
+        }
+    }
+
+    fn market_data() -> MarketData {
+        MarketData::new(vec![
+            Arc::new(MockProvider {
+                provider: Provider::Finnhub,
+                operations: &[Operation::Quote],
+                rate_limited: &["AAPL"],
+            }),
+            Arc::new(MockProvider {
+                provider: Provider::AlphaVantage,
+                operations: &[Operation::Quote, Operation::MarketStatus],
+                rate_limited: &[],
+            }),
+        ])
+    }
+
+    fn market(symbols: &[&str]) -> Vec<Constituent> {
+        symbols
+            .iter()
+            .map(|symbol| Constituent {
+                symbol: symbol.to_string(),
+                name: symbol.to_string(),
+            })
+            .collect()
+    }
+
+    fn providers(market_data: &MarketData, operation: Operation) -> Vec<Provider> {
+        market_data
+            .providers_for(operation)
+            .iter()
+            .map(|provider| provider.provider())
+            .collect()
+    }
+
+    #[test]
+    fn orders_providers_by_priority() {
+        let mut market_data = market_data();
+        assert_eq!(
+            providers(&market_data, Operation::Quote),
+            [Provider::Finnhub, Provider::AlphaVantage]
+        );
+        assert_eq!(
+            providers(&market_data, Operation::MarketStatus),
+            [Provider::AlphaVantage]
+        );
+        assert!(providers(&market_data, Operation::Candles).is_empty());
+
+        market_data
+            .priority(vec![Provider::AlphaVantage, Provider::Finnhub])
+            .operation_priority(Operation::MarketStatus, vec![Provider::Finnhub]);
+        assert_eq!(
+            providers(&market_data, Operation::Quote),
+            [Provider::AlphaVantage, Provider::Finnhub]
+        );
+        assert!(providers(&market_data, Operation::MarketStatus).is_empty());
+    }
+
+    #[tokio::test]
+    async fn serve_falls_back_if_the_provider_is_unavailable() {
+        let market_data = market_data();
+
+        let (provider, quote) = market_data
+            .serve(Operation::Quote, |provider| provider.quote("AAPL", "Apple"))
+            .await
+            .unwrap();
+        assert_eq!(provider, Provider::AlphaVantage);
+        assert_eq!(quote.provider, Provider::AlphaVantage);
+
+        let (provider, _) = market_data
+            .serve(Operation::Quote, |provider| {
+                provider.quote("MSFT", "Microsoft")
+            })
+            .await
+            .unwrap();
+        assert_eq!(provider, Provider::Finnhub);
+
+        let err = market_data
+            .serve(Operation::Candles, |provider| provider.market_news())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), "unsupported_operation");
+    }
+
+    #[tokio::test]
+    async fn serve_does_not_fall_back_on_errors_about_the_data() {
+        let err = market_data()
+            .serve(Operation::Quote, |provider| provider.quote("UNKNOWN", ""))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), "symbol_not_found");
+        assert_eq!(err.provider(), Some(Provider::Finnhub));
+    }
+
+    #[tokio::test]
+    async fn quotes_fall_back_per_symbol() {
+        let market_quotes = market_data()
+            .quotes(&market(&["AAPL", "MSFT", "UNKNOWN"]))
+            .await
+            .unwrap();
+
+        let quotes: Vec<(&str, Provider)> = market_quotes
+            .quotes
+            .iter()
+            .map(|quote| (quote.symbol.as_str(), quote.provider))
+            .collect();
+        assert_eq!(
+            quotes,
+            [
+                ("MSFT", Provider::Finnhub),
+                ("AAPL", Provider::AlphaVantage)
+            ]
+        );
+        assert_eq!(market_quotes.failures.len(), 1);
+        assert_eq!(market_quotes.failures[0].symbol, "UNKNOWN");
+    }
+
+    #[tokio::test]
+    async fn quotes_keep_failures_of_the_last_provider() {
+        let mut market_data = market_data();
+        market_data.priority(vec![Provider::Finnhub]);
+
+        let market_quotes = market_data.quotes(&market(&["AAPL"])).await.unwrap();
+
+        assert!(market_quotes.quotes.is_empty());
+        assert_eq!(market_quotes.failures[0].error.code(), "rate_limited");
+    }
+
+    #[tokio::test]
+    async fn quotes_fall_back_for_a_limited_number_of_symbols() {
+        let symbols = ["A", "B", "C", "D", "E", "F", "G", "H"];
+        let mut market_data = MarketData::new(vec![
+            Arc::new(MockProvider {
+                provider: Provider::Finnhub,
+                operations: &[Operation::Quote],
+                rate_limited: &["A", "B", "C", "D", "E", "F", "G", "H"],
+            }),
+            Arc::new(MockProvider {
+                provider: Provider::AlphaVantage,
+                operations: &[Operation::Quote],
+                rate_limited: &[],
+            }),
+        ]);
+        market_data.max_batch_fallback(3);
+
+        let market_quotes = market_data.quotes(&market(&symbols)).await.unwrap();
+        let fallbacks: Vec<&str> = market_quotes
+            .quotes
+            .iter()
+            .map(|quote| quote.symbol.as_str())
+            .collect();
+        assert_eq!(fallbacks, ["A", "B", "C"]);
+        assert_eq!(market_quotes.failures.len(), 5);
+        assert!(market_quotes
+            .failures
+            .iter()
+            .all(|failure| failure.error.code() == "rate_limited"));
+
+        let market_quotes = market_data
+            .quotes_without_fallback(&market(&symbols))
+            .await
+            .unwrap();
+        assert!(market_quotes.quotes.is_empty());
+        assert_eq!(market_quotes.failures.len(), symbols.len());
+    }
+
+    #[test]
+    fn parses_priority_lists() {
+        assert_eq!(
+            parse_priority("AlphaVantage, finnhub,alphavantage").unwrap(),
+            [Provider::AlphaVantage, Provider::Finnhub]
+        );
+        assert!(parse_priority("finnhub,yahoo").is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::broadcast;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
+
+use crate::{
+    finnhub_api::symbol_quote::SymbolQuoteFrontend,
+    indices::Constituent,
+    quotes::{summarize_quotes, QuoteSummary},
+    AppState,
+};
+
+/**
+ * Lower bound of the poll interval, matches the cache TTL of the quote routes.
+ */
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(15);
+/**
+ * Share of the Finnhub rate limit the pollers may use, the rest is left to the other routes.
+ */
+const RATE_LIMIT_SHARE: f64 = 0.5;
+/**
+ * Events a slow subscriber may fall behind before it gets a fresh snapshot.
+ */
+const CHANNEL_CAPACITY: usize = 16;
+
+/**
+ * Gets pushed to the subscribers of an index stream.
+ */
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /**
+     * The full quotes of the index, sent on connect and after falling behind
+     */
+    Snapshot(Arc<QuoteSummary>),
+    /**
+     * Only the quotes which changed since the last poll
+     */
+    Update(Arc<QuoteUpdate>),
+    /**
+     * The poll failed for every symbol, the last snapshot stays valid
+     */
+    Error(Arc<Value>),
+}
+
+#[derive(Serialize, Debug)]
+pub struct QuoteUpdate {
+    pub changed: Vec<SymbolQuoteFrontend>,
+    pub sentiment: &'static str,
+    pub avg_percentage_gains: f32,
+    pub avg_percentage_losses: f32,
+    /**
+     * Symbols of the gainers, sorted descending
+     */
+    pub gainers: Vec<String>,
+    /**
+     * Symbols of the losers, sorted ascending
+     */
+    pub losers: Vec<String>,
+    pub failed: Vec<Value>,
+    pub rate_limit_remaining: Option<u32>,
+    pub rate_limit_reset: Option<u128>,
+}
+
+#[derive(Debug)]
+struct IndexStream {
+    sender: broadcast::Sender<StreamEvent>,
+    latest: Mutex<Option<Arc<QuoteSummary>>>,
+}
+
+/**
+ * Background pollers for the quote streams, at most one per index no matter how many
+ * clients are subscribed. A poller is started by the first subscriber and stops after the
+ * last one left.
+ */
+#[derive(Debug, Default)]
+pub struct QuoteStreams {
+    streams: Mutex<HashMap<String, Arc<IndexStream>>>,
+    /**
+     * Symbols of all running pollers, used to split the rate limit between them
+     */
+    active_symbols: AtomicUsize,
+}
+
+/**
+ * Subscription to the stream of an index.
+ */
+pub struct QuoteSubscription {
+    receiver: broadcast::Receiver<StreamEvent>,
+    stream: Arc<IndexStream>,
+}
+
+impl QuoteSubscription {
+    /**
+     * Events of the index, starting with the latest snapshot if the index was already polled.
+     * A subscriber which fell behind gets a fresh snapshot instead of the missed updates.
+     */
+    pub fn into_stream(self) -> impl Stream<Item = StreamEvent> {
+        let latest = self.stream.latest.lock().unwrap().clone();
+        let stream = self.stream;
+
+        let updates = BroadcastStream::new(self.receiver).filter_map(move |event| match event {
+            Ok(event) => Some(event),
+            Err(BroadcastStreamRecvError::Lagged(_)) => stream
+                .latest
+                .lock()
+                .unwrap()
+                .clone()
+                .map(StreamEvent::Snapshot),
+        });
+
+        tokio_stream::iter(latest.map(StreamEvent::Snapshot)).chain(updates)
+    }
+}
+
+impl QuoteStreams {
+    /**
+     * Subscribes to the quotes of the given index, starting its poller if necessary.
+     */
+    pub fn subscribe(
+        &self,
+        state: &Arc<AppState>,
+        index: &str,
+        constituents: &[Constituent],
+    ) -> QuoteSubscription {
+        let mut streams = self.streams.lock().unwrap();
+
+        if let Some(stream) = streams.get(index) {
+            return QuoteSubscription {
+                receiver: stream.sender.subscribe(),
+                stream: stream.clone(),
+            };
+        }
+
+        let (sender, receiver) = broadcast::channel(CHANNEL_CAPACITY);
+        let stream = Arc::new(IndexStream {
+            sender,
+            latest: Mutex::new(None),
+        });
+        streams.insert(index.to_string(), stream.clone());
+        self.active_symbols
+            .fetch_add(constituents.len(), Ordering::Relaxed);
+
+        tokio::spawn(poll_index(
+            state.clone(),
+            index.to_string(),
+            constituents.to_vec(),
+            stream.clone(),
+        ));
+
+        QuoteSubscription { receiver, stream }
+    }
+
+    /**
+     * Removes the stream if nobody is subscribed anymore. Returns whether the poller should stop.
+     */
+    fn remove_if_unused(&self, index: &str, symbols: usize) -> bool {
+        let mut streams = self.streams.lock().unwrap();
+        let unused = streams
+            .get(index)
+            .is_some_and(|stream| stream.sender.receiver_count() == 0);
+
+        if unused {
+            streams.remove(index);
+            self.active_symbols.fetch_sub(symbols, Ordering::Relaxed);
+        }
+
+        unused
+    }
+
+    /**
+     * Time between two polls, so all running pollers together stay within their share of
+     * the Finnhub rate limit.
+     */
+    fn poll_interval(&self, request_interval: Duration) -> Duration {
+        let symbols = self.active_symbols.load(Ordering::Relaxed) as u32;
+        request_interval
+            .saturating_mul(symbols)
+            .div_f64(RATE_LIMIT_SHARE)
+            .max(MIN_POLL_INTERVAL)
+    }
+}
+
+async fn poll_index(
+    state: Arc<AppState>,
+    index: String,
+    constituents: Vec<Constituent>,
+    stream: Arc<IndexStream>,
+) {
+    let mut previous: Option<Arc<QuoteSummary>> = None;
+
+    loop {
+        let summary = state
+            .market_data
+            // Falling back on every poll would use up the Alpha Vantage quota in minutes
+            .quotes_without_fallback(&constituents)
+            .await
+            .and_then(summarize_quotes);
+
+        let event = match summary {
+            Ok(summary) => {
+                let summary = Arc::new(summary);
+                *stream.latest.lock().unwrap() = Some(summary.clone());
+
+                let event = match &previous {
+                    None => Some(StreamEvent::Snapshot(summary.clone())),
+                    Some(previous) => quote_update(previous, &summary)
+                        .map(|update| StreamEvent::Update(Arc::new(update))),
+                };
+                previous = Some(summary);
+                event
+            }
+            Err(err) => Some(StreamEvent::Error(Arc::new(serde_json::json!({
+                "code": err.code(),
+                "message": err.to_string(),
+            })))),
+        };
+
+        if let Some(event) = event {
+            // Fails only without subscribers, which is checked below
+            let _ = stream.sender.send(event);
+        }
+
+        let interval = state
+            .quote_streams
+            .poll_interval(state.finnhub.request_interval());
+        tokio::time::sleep(interval).await;
+
+        // Stop once the last subscriber left
+        if state
+            .quote_streams
+            .remove_if_unused(&index, constituents.len())
+        {
+            return;
+        }
+    }
+}
+
+/**
+ * Differences between two polls, `None` if nothing changed.
+ */
+fn quote_update(previous: &QuoteSummary, current: &QuoteSummary) -> Option<QuoteUpdate> {
+    let previous_quotes: HashMap<&str, &SymbolQuoteFrontend> = previous
+        .gainers
+        .iter()
+        .chain(previous.losers.iter())
+        .map(|quote| (quote.symbol.as_str(), quote))
+        .collect();
+
+    let changed: Vec<SymbolQuoteFrontend> = current
+        .gainers
+        .iter()
+        .chain(current.losers.iter())
+        .filter(|quote| previous_quotes.get(quote.symbol.as_str()) != Some(quote))
+        .cloned()
+        .collect();
+
+    let failed_symbols = |summary: &QuoteSummary| -> HashSet<String> {
+        summary
+            .failed
+            .iter()
+            .filter_map(|failure| failure["symbol"].as_str().map(String::from))
+            .collect()
+    };
+
+    if changed.is_empty() && failed_symbols(previous) == failed_symbols(current) {
+        return None;
+    }
+
+    let symbols = |quotes: &[SymbolQuoteFrontend]| -> Vec<String> {
+        quotes.iter().map(|quote| quote.symbol.clone()).collect()
+    };
+
+    Some(QuoteUpdate {
+        changed,
+        sentiment: current.sentiment,
+        avg_percentage_gains: current.avg_percentage_gains,
+        avg_percentage_losses: current.avg_percentage_losses,
+        gainers: symbols(&current.gainers),
+        losers: symbols(&current.losers),
+        failed: current.failed.clone(),
+        rate_limit_remaining: current.rate_limit_remaining,
+        rate_limit_reset: current.rate_limit_reset,
+    })
+}
@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    error::ApiError,
+    finnhub_api::{
+        lib::RateLimitInfo,
+        symbol_quote::{MarketQuotes, SymbolQuoteFrontend},
+    },
+};
+
+/**
+ * Aggregates the quotes of a basket of symbols (an index or a watchlist) into the gainers,
+ * losers and overall sentiment returned by the quote routes.
+ */
+pub fn summarize_quotes(market_quotes: MarketQuotes) -> Result<QuoteSummary, ApiError> {
+    let QuoteBatch {
+        quotes,
+        failed,
+        rate_limit_remaining,
+        rate_limit_reset,
+    } = QuoteBatch::try_from(market_quotes)?;
+
+    let mut quote_gainers: Vec<SymbolQuoteFrontend> = quotes
+        .iter()
+        .filter(|x| x.delta_percent.is_sign_positive())
+        .cloned()
+        .collect();
+
+    // Sort descending
+    quote_gainers.sort_by(|a, b| b.delta_percent.total_cmp(&a.delta_percent));
+
+    let mut quote_losers: Vec<SymbolQuoteFrontend> = quotes
+        .iter()
+        .filter(|x| x.delta_percent.is_sign_negative())
+        .cloned()
+        .collect();
+
+    // Sort ascending
+    quote_losers.sort_by(|a, b| a.delta_percent.total_cmp(&b.delta_percent));
+
+    let sentiment = match quote_gainers.len().cmp(&quote_losers.len()) {
+        Ordering::Greater => "bullish",
+        Ordering::Less => "bearish",
+        Ordering::Equal => "neutral",
+    };
+
+    let gains_percentage_sum: f32 = quote_gainers.iter().map(|q| q.delta_percent).sum();
+    let losses_percentage_sum: f32 = quote_losers.iter().map(|q| q.delta_percent).sum();
+    let avg_percentage_gains = average(gains_percentage_sum, quote_gainers.len());
+    let avg_percentage_losses = average(losses_percentage_sum, quote_losers.len());
+
+    Ok(QuoteSummary {
+        sentiment,
+        avg_percentage_gains,
+        avg_percentage_losses,
+        gainers: quote_gainers,
+        losers: quote_losers,
+        failed,
+        rate_limit_remaining,
+        rate_limit_reset,
+    })
+}
+
+/**
+ * Response of the quote routes for an index or a watchlist.
+ */
+#[derive(Serialize, Debug, Clone)]
+pub struct QuoteSummary {
+    pub sentiment: &'static str,
+    pub avg_percentage_gains: f32,
+    pub avg_percentage_losses: f32,
+    pub gainers: Vec<SymbolQuoteFrontend>,
+    pub losers: Vec<SymbolQuoteFrontend>,
+    /**
+     * `{ symbol, name, code, message }` of every symbol whose quote could not be fetched
+     */
+    pub failed: Vec<Value>,
+    pub rate_limit_remaining: Option<u32>,
+    pub rate_limit_reset: Option<u128>,
+}
+
+/**
+ * Fetched quotes in the frontend shape, with the failed symbols and the lowest Finnhub rate
+ * limit seen while fetching them.
+ */
+pub struct QuoteBatch {
+    pub quotes: Vec<SymbolQuoteFrontend>,
+    pub failed: Vec<Value>,
+    pub rate_limit_remaining: Option<u32>,
+    pub rate_limit_reset: Option<u128>,
+}
+
+impl TryFrom<MarketQuotes> for QuoteBatch {
+    type Error = ApiError;
+
+    fn try_from(market_quotes: MarketQuotes) -> Result<Self, Self::Error> {
+        let mut failures = market_quotes.failures.into_iter();
+
+        // Nothing to return if every symbol failed
+        if market_quotes.quotes.is_empty() {
+            if let Some(failure) = failures.next() {
+                return Err(failure.error);
+            }
+        }
+
+        let failed: Vec<Value> = failures
+            .map(|failure| {
+                json!({
+                    "symbol": failure.symbol,
+                    "name": failure.name,
+                    "code": failure.error.code(),
+                    "message": failure.error.to_string(),
+                })
+            })
+            .collect();
+
+        let rate_limit_infos: Vec<&RateLimitInfo> = market_quotes
+            .quotes
+            .iter()
+            .filter_map(|q| q.rate_limit_info.as_ref())
+            .collect();
+
+        let rate_limit_remaining = rate_limit_infos
+            .iter()
+            .filter_map(|info| info.ratelimit_remaining.parse::<u32>().ok())
+            .min();
+
+        let rate_limit_reset = rate_limit_infos
+            .last()
+            .and_then(|info| info.ratelimit_reset.parse::<u128>().ok());
+
+        let quotes: Vec<SymbolQuoteFrontend> = market_quotes
+            .quotes
+            .into_iter()
+            .map(SymbolQuoteFrontend::from)
+            .collect();
+
+        Ok(QuoteBatch {
+            quotes,
+            failed,
+            rate_limit_remaining,
+            rate_limit_reset,
+        })
+    }
+}
+
+fn average(sum: f32, count: usize) -> f32 {
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f32
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::candles::{unix_now, SECONDS_PER_DAY};
+
+#[derive(Debug)]
+struct BucketState {
+    /**
+     * Can become negative: every waiting caller reserves the next free token.
+     */
+    tokens: f64,
+    last_refill: Instant,
+    blocked_until: Option<Instant>,
+}
+
+/**
+ * Token bucket pacing outgoing requests.
+ *
+ * Callers wait for the next free token as long as the wait is shorter than `max_wait` and
+ * fail fast with the required wait time otherwise. The bucket also follows the
+ * `X-Ratelimit-Remaining`/`X-Ratelimit-Reset` headers sent by Finnhub.
+ */
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_interval: Duration,
+    max_wait: Duration,
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    /**
+     * `capacity` is the maximum burst size, `per_minute` the number of tokens added per minute.
+     */
+    pub fn new(capacity: u32, per_minute: u32, max_wait: Duration) -> TokenBucket {
+        TokenBucket {
+            capacity: capacity as f64,
+            refill_interval: Duration::from_secs(60) / per_minute.max(1),
+            max_wait,
+            state: Mutex::new(BucketState {
+                tokens: capacity as f64,
+                last_refill: Instant::now(),
+                blocked_until: None,
+            }),
+        }
+    }
+
+    /**
+     * Time it takes to refill a single token.
+     */
+    pub fn refill_interval(&self) -> Duration {
+        self.refill_interval
+    }
+
+    fn refill(&self, state: &mut BucketState, now: Instant) {
+        let elapsed = now.duration_since(state.last_refill);
+        let new_tokens = elapsed.as_secs_f64() / self.refill_interval.as_secs_f64();
+
+        state.tokens = (state.tokens + new_tokens).min(self.capacity);
+        state.last_refill = now;
+    }
+
+    /**
+     * Waits until a request may be sent. Returns the time until the next free token as
+     * error if it exceeds the maximum wait time.
+     */
+    pub async fn acquire(&self) -> Result<(), Duration> {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            self.refill(&mut state, now);
+
+            let blocked_for = state
+                .blocked_until
+                .map(|until| until.saturating_duration_since(now))
+                .unwrap_or_default();
+            let token_wait = if state.tokens >= 1.0 {
+                Duration::ZERO
+            } else {
+                self.refill_interval.mul_f64(1.0 - state.tokens)
+            };
+            let wait = blocked_for.max(token_wait);
+
+            if wait > self.max_wait {
+                return Err(wait);
+            }
+
+            state.tokens -= 1.0;
+            wait
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Syncs the bucket with the remaining calls and the reset time (unix seconds) reported
+     * by the upstream API.
+     */
+    pub fn update(&self, remaining: u32, reset: u64) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        self.refill(&mut state, now);
+
+        state.tokens = state.tokens.min(remaining as f64);
+
+        if remaining == 0 {
+            let reset_in = Duration::from_secs(reset.saturating_sub(unix_now() as u64));
+            state.blocked_until = Some(now + reset_in);
+        }
+    }
+
+    /**
+     * Holds back all requests for the given duration, e.g. after an HTTP 429 response.
+     */
+    pub fn block_for(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.blocked_until = Some(Instant::now() + duration);
+    }
+}
+
+#[derive(Debug)]
+struct QuotaState {
+    used: u32,
+    day: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct QuotaUsage {
+    pub used: u32,
+    pub limit: u32,
+    /**
+     * Seconds until midnight UTC
+     */
+    pub resets_in: u64,
+}
+
+/**
+ * Fixed number of requests per day, reset at midnight UTC. Used for Alpha Vantage which
+ * only allows 25 calls per day on the free plan.
+ */
+#[derive(Debug)]
+pub struct DailyQuota {
+    limit: u32,
+    state: Mutex<QuotaState>,
+}
+
+impl DailyQuota {
+    pub fn new(limit: u32) -> DailyQuota {
+        DailyQuota {
+            limit,
+            state: Mutex::new(QuotaState {
+                used: 0,
+                day: today(),
+            }),
+        }
+    }
+
+    /**
+     * `today` is the number of days since the unix epoch, i.e. days start at midnight UTC.
+     */
+    fn reset_if_new_day(state: &mut QuotaState, today: i64) {
+        if state.day != today {
+            state.day = today;
+            state.used = 0;
+        }
+    }
+
+    /**
+     * Takes one call from the quota. Returns the time until the quota resets as error if it
+     * is used up.
+     */
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        Self::reset_if_new_day(&mut state, today());
+
+        if state.used >= self.limit {
+            return Err(Self::until_reset(unix_now()));
+        }
+
+        state.used += 1;
+        Ok(())
+    }
+
+    pub fn usage(&self) -> QuotaUsage {
+        let mut state = self.state.lock().unwrap();
+        Self::reset_if_new_day(&mut state, today());
+
+        QuotaUsage {
+            used: state.used,
+            limit: self.limit,
+            resets_in: Self::until_reset(unix_now()).as_secs(),
+        }
+    }
+
+    /**
+     * Marks the quota as used up, e.g. after the upstream API reported an exhausted limit.
+     */
+    pub fn exhaust(&self) {
+        let mut state = self.state.lock().unwrap();
+        Self::reset_if_new_day(&mut state, today());
+        state.used = self.limit;
+    }
+
+    fn until_reset(now: i64) -> Duration {
+        let next_day = (now.div_euclid(SECONDS_PER_DAY) + 1) * SECONDS_PER_DAY;
+        Duration::from_secs((next_day - now) as u64)
+    }
+}
+
+/**
+ * Days since the unix epoch, a new day starts at midnight UTC.
+ */
+fn today() -> i64 {
+    unix_now().div_euclid(SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * Midnight UTC of 2023-03-01
+     */
+    const MIDNIGHT: i64 = 19_417 * SECONDS_PER_DAY;
+
+    #[tokio::test]
+    async fn paces_requests_after_the_burst() {
+        // A token every 100ms
+        let bucket = TokenBucket::new(2, 600, Duration::from_secs(1));
+
+        let start = Instant::now();
+        bucket.acquire().await.unwrap();
+        bucket.acquire().await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        bucket.acquire().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[tokio::test]
+    async fn fails_fast_if_the_wait_exceeds_the_maximum() {
+        let bucket = TokenBucket::new(1, 60, Duration::from_millis(100));
+        bucket.acquire().await.unwrap();
+
+        let start = Instant::now();
+        let wait = bucket.acquire().await.unwrap_err();
+
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn follows_the_upstream_rate_limit() {
+        let bucket = TokenBucket::new(30, 60, Duration::from_millis(100));
+
+        bucket.update(1, 0);
+        bucket.acquire().await.unwrap();
+        assert!(bucket.acquire().await.is_err());
+
+        let bucket = TokenBucket::new(30, 60, Duration::from_millis(100));
+        bucket.update(0, unix_now() as u64 + 10);
+        let wait = bucket.acquire().await.unwrap_err();
+        assert!(wait > Duration::from_secs(8) && wait <= Duration::from_secs(10));
+
+        let bucket = TokenBucket::new(30, 60, Duration::from_millis(100));
+        bucket.block_for(Duration::from_secs(5));
+        assert!(bucket.acquire().await.unwrap_err() > Duration::from_secs(4));
+    }
+
+    #[test]
+    fn quota_is_used_up_after_the_limit() {
+        let quota = DailyQuota::new(2);
+
+        assert!(quota.try_acquire().is_ok());
+        assert!(quota.try_acquire().is_ok());
+        let wait = quota.try_acquire().unwrap_err();
+
+        assert!(wait <= Duration::from_secs(SECONDS_PER_DAY as u64));
+        assert_eq!(quota.usage().used, 2);
+    }
+
+    #[test]
+    fn quota_resets_at_midnight_utc() {
+        let quota = DailyQuota::new(25);
+        quota.exhaust();
+
+        let mut state = quota.state.lock().unwrap();
+        let today = state.day;
+        DailyQuota::reset_if_new_day(&mut state, today);
+        assert_eq!(state.used, 25);
+
+        DailyQuota::reset_if_new_day(&mut state, today + 1);
+        assert_eq!(state.used, 0);
+        assert_eq!(state.day, today + 1);
+    }
+
+    #[test]
+    fn quota_reset_is_the_next_midnight_utc() {
+        assert_eq!(
+            DailyQuota::until_reset(MIDNIGHT - 1),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            DailyQuota::until_reset(MIDNIGHT),
+            Duration::from_secs(SECONDS_PER_DAY as u64)
+        );
+        assert_eq!(
+            DailyQuota::until_reset(MIDNIGHT + 6 * 60 * 60),
+            Duration::from_secs(18 * 60 * 60)
+        );
+    }
+}
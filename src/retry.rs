@@ -0,0 +1,299 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rand::Rng;
+
+/**
+ * Implemented by the upstream API errors to tell the `RetryPolicy` what went wrong.
+ */
+pub trait Retryable {
+    /**
+     * HTTP status code of the failed upstream response.
+     */
+    fn status(&self) -> Option<u16>;
+    /**
+     * Whether the request failed without a response, e.g. a timeout or a connection reset.
+     */
+    fn is_transient(&self) -> bool;
+    /**
+     * Wait time requested by the upstream API, e.g. via a `Retry-After` header.
+     */
+    fn retry_after(&self) -> Option<Duration>;
+}
+
+/**
+ * Counters of a `RetryPolicy`, shared by all clones of the policy.
+ */
+#[derive(Debug, Default)]
+pub struct RetryStats {
+    requests: AtomicU64,
+    retries: AtomicU64,
+    exhausted: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryStatsSnapshot {
+    /**
+     * Upstream calls, not counting retries
+     */
+    pub requests: u64,
+    pub retries: u64,
+    /**
+     * Calls which still failed after the last attempt
+     */
+    pub exhausted: u64,
+}
+
+impl RetryStats {
+    pub fn snapshot(&self) -> RetryStatsSnapshot {
+        RetryStatsSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/**
+ * Retries failed upstream calls with exponential backoff.
+ *
+ * A call is retried if it failed without a response or with one of the `retry_on` status
+ * codes. The delay doubles with every attempt up to `max_delay`. With `jitter` the delay is
+ * picked randomly between half and the full delay. A `Retry-After` from upstream replaces
+ * the computed delay, the call is given up if it is longer than `max_delay`.
+ */
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: bool,
+    pub retry_on: Vec<u16>,
+    stats: Arc<RetryStats>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            retry_on: vec![429, 500, 502, 503, 504],
+            stats: Arc::new(RetryStats::default()),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn stats(&self) -> RetryStatsSnapshot {
+        self.stats.snapshot()
+    }
+
+    /**
+     * Delay before the given retry (starting at 1), or `None` if the error should not be
+     * retried.
+     */
+    fn delay<E: Retryable>(&self, retry: u32, err: &E) -> Option<Duration> {
+        let retryable = err.is_transient()
+            || err
+                .status()
+                .is_some_and(|status| self.retry_on.contains(&status));
+        if !retryable {
+            return None;
+        }
+
+        if let Some(retry_after) = err.retry_after() {
+            return (retry_after <= self.max_delay).then_some(retry_after);
+        }
+
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry - 1))
+            .min(self.max_delay);
+
+        if self.jitter {
+            let factor = rand::thread_rng().gen_range(0.5..=1.0);
+            Some(exponential.mul_f64(factor))
+        } else {
+            Some(exponential)
+        }
+    }
+
+    pub async fn run<T, E, F, Fut>(&self, mut f: F) -> Result<T, E>
+    where
+        E: Retryable,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.stats.requests.fetch_add(1, Ordering::Relaxed);
+        let mut attempt = 1;
+
+        loop {
+            let err = match f().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+
+            let delay = match self.delay(attempt, &err) {
+                Some(delay) if attempt < self.max_attempts => delay,
+                Some(_) => {
+                    self.stats.exhausted.fetch_add(1, Ordering::Relaxed);
+                    return Err(err);
+                }
+                None => return Err(err),
+            };
+
+            self.stats.retries.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum TestError {
+        Status(u16),
+        Timeout,
+        RetryAfter(Duration),
+    }
+
+    impl Retryable for TestError {
+        fn status(&self) -> Option<u16> {
+            match self {
+                Self::Status(status) => Some(*status),
+                Self::RetryAfter(_) => Some(429),
+                Self::Timeout => None,
+            }
+        }
+
+        fn is_transient(&self) -> bool {
+            matches!(self, Self::Timeout)
+        }
+
+        fn retry_after(&self) -> Option<Duration> {
+            match self {
+                Self::RetryAfter(duration) => Some(*duration),
+                _ => None,
+            }
+        }
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+            jitter: false,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(1),
+            ..policy()
+        };
+
+        let delays: Vec<Option<Duration>> = (1..=4)
+            .map(|retry| policy.delay(retry, &TestError::Timeout))
+            .collect();
+
+        assert_eq!(
+            delays,
+            [250, 500, 1000, 1000].map(|ms| Some(Duration::from_millis(ms)))
+        );
+    }
+
+    #[test]
+    fn jitters_between_half_and_the_full_delay() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(400),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            ..policy()
+        };
+
+        for _ in 0..100 {
+            let delay = policy.delay(2, &TestError::Status(503)).unwrap();
+            assert!(delay >= Duration::from_millis(400) && delay <= Duration::from_millis(800));
+        }
+    }
+
+    #[test]
+    fn only_retries_transient_errors_and_listed_statuses() {
+        let policy = policy();
+
+        assert!(policy.delay(1, &TestError::Timeout).is_some());
+        assert!(policy.delay(1, &TestError::Status(503)).is_some());
+        assert!(policy.delay(1, &TestError::Status(404)).is_none());
+        assert_eq!(
+            policy.delay(1, &TestError::RetryAfter(Duration::from_millis(5))),
+            Some(Duration::from_millis(5))
+        );
+        // Waiting longer than the maximum delay isn't worth it
+        assert!(policy
+            .delay(1, &TestError::RetryAfter(Duration::from_secs(60)))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn stops_after_the_last_attempt() {
+        let policy = policy();
+        let calls = AtomicU32::new(0);
+
+        let result: Result<(), _> = policy
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(TestError::Status(503))
+            })
+            .await;
+
+        assert_eq!(result, Err(TestError::Status(503)));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let stats = policy.stats();
+        assert_eq!((stats.requests, stats.retries, stats.exhausted), (1, 2, 1));
+    }
+
+    #[tokio::test]
+    async fn stops_on_success_and_final_errors() {
+        let policy = policy();
+        let calls = AtomicU32::new(0);
+
+        let result = policy
+            .run(|| async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(TestError::Timeout),
+                    _ => Ok("quote"),
+                }
+            })
+            .await;
+        assert_eq!(result, Ok("quote"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let result: Result<(), _> = policy
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(TestError::Status(400))
+            })
+            .await;
+        assert_eq!(result, Err(TestError::Status(400)));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let stats = policy.stats();
+        assert_eq!((stats.requests, stats.retries, stats.exhausted), (2, 1, 0));
+    }
+}
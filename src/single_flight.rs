@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::broadcast;
+
+type InFlight<T> = Mutex<HashMap<String, broadcast::Sender<T>>>;
+
+/**
+ * Deduplicates concurrent calls for the same key.
+ *
+ * The first caller for a key runs the future, every caller arriving while it is still in
+ * flight waits for and receives a clone of its result.
+ */
+#[derive(Debug)]
+pub struct SingleFlight<T> {
+    in_flight: Arc<InFlight<T>>,
+}
+
+impl<T> Clone for SingleFlight<T> {
+    fn clone(&self) -> Self {
+        SingleFlight {
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+impl<T> Default for SingleFlight<T> {
+    fn default() -> Self {
+        SingleFlight {
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+/**
+ * Removes the key of the leading call once it finished or got cancelled. Waiting callers of
+ * a cancelled call see a closed channel and run the future on their own.
+ */
+struct LeaderGuard<'a, T> {
+    in_flight: &'a InFlight<T>,
+    key: &'a str,
+    finished: bool,
+}
+
+impl<T> Drop for LeaderGuard<'_, T> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.in_flight.lock().unwrap().remove(self.key);
+        }
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub fn new() -> SingleFlight<T> {
+        SingleFlight::default()
+    }
+
+    pub async fn run<F, Fut>(&self, key: &str, f: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let waiting = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(key) {
+                Some(sender) => Some(sender.subscribe()),
+                None => {
+                    let (sender, _) = broadcast::channel(1);
+                    in_flight.insert(key.to_string(), sender);
+                    None
+                }
+            }
+        };
+
+        if let Some(mut receiver) = waiting {
+            return match receiver.recv().await {
+                Ok(value) => value,
+                Err(_) => f().await,
+            };
+        }
+
+        let mut guard = LeaderGuard {
+            in_flight: &self.in_flight,
+            key,
+            finished: false,
+        };
+        let value = f().await;
+
+        let sender = self.in_flight.lock().unwrap().remove(key);
+        guard.finished = true;
+        if let Some(sender) = sender {
+            // Fails only if nobody is waiting
+            let _ = sender.send(value.clone());
+        }
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    /**
+     * A call which takes a while and counts how often it ran.
+     */
+    async fn slow_call(calls: &AtomicU32, value: &str) -> String {
+        calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        value.to_string()
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_call() {
+        let flight = SingleFlight::new();
+        let calls = AtomicU32::new(0);
+
+        let values = futures_util::future::join_all(
+            (0..5).map(|_| flight.run("quote:AAPL", || slow_call(&calls, "AAPL"))),
+        )
+        .await;
+
+        assert_eq!(values, vec!["AAPL"; 5]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn runs_different_and_later_calls_on_their_own() {
+        let flight = SingleFlight::new();
+        let calls = AtomicU32::new(0);
+
+        let (aapl, msft) = tokio::join!(
+            flight.run("quote:AAPL", || slow_call(&calls, "AAPL")),
+            flight.run("quote:MSFT", || slow_call(&calls, "MSFT")),
+        );
+        let again = flight.run("quote:AAPL", || slow_call(&calls, "AAPL")).await;
+
+        assert_eq!(
+            (aapl.as_str(), msft.as_str(), again.as_str()),
+            ("AAPL", "MSFT", "AAPL")
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn waiting_callers_run_the_call_if_the_leader_is_cancelled() {
+        let flight = SingleFlight::new();
+
+        let leader = tokio::spawn({
+            let flight = flight.clone();
+            async move {
+                flight
+                    .run("quote:AAPL", || async {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        "leader".to_string()
+                    })
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let follower = tokio::spawn({
+            let flight = flight.clone();
+            async move {
+                flight
+                    .run("quote:AAPL", || async { "follower".to_string() })
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        leader.abort();
+
+        assert_eq!(follower.await.unwrap(), "follower");
+        assert!(flight.in_flight.lock().unwrap().is_empty());
+    }
+}
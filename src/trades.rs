@@ -0,0 +1,493 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{
+    net::TcpStream,
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{self, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::logging;
+
+/**
+ * Finnhub allows 50 symbols per WebSocket connection on the free plan.
+ */
+const MAX_UPSTREAM_SYMBOLS: usize = 50;
+/**
+ * Trade batches a client may fall behind before further batches are dropped for it.
+ */
+const CLIENT_CHANNEL_CAPACITY: usize = 256;
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+type Upstream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum TradesError {
+    #[error("At most {MAX_UPSTREAM_SYMBOLS} symbols can be streamed at the same time")]
+    TooManySymbols,
+    #[error("The trades proxy is not running")]
+    ProxyStopped,
+}
+
+/**
+ * Gets sent by Finnhub, https://finnhub.io/docs/api/websocket-trades
+ */
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Trade {
+    #[serde(rename(deserialize = "s"))]
+    pub symbol: String,
+    #[serde(rename(deserialize = "p"))]
+    pub price: f64,
+    /**
+     * UNIX milliseconds
+     */
+    #[serde(rename(deserialize = "t"))]
+    pub timestamp: u64,
+    #[serde(rename(deserialize = "v"))]
+    pub volume: f64,
+    #[serde(rename(deserialize = "c"), default)]
+    pub conditions: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum UpstreamMessage {
+    Trade {
+        data: Vec<Trade>,
+    },
+    Error {
+        msg: String,
+    },
+    // e.g. {"type":"ping"}
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug)]
+enum Command {
+    Register {
+        client: u64,
+        trades: mpsc::Sender<Vec<Trade>>,
+    },
+    Subscribe {
+        client: u64,
+        symbol: String,
+        reply: oneshot::Sender<Result<(), TradesError>>,
+    },
+    Unsubscribe {
+        client: u64,
+        symbol: String,
+    },
+    Unregister {
+        client: u64,
+    },
+}
+
+/**
+ * Proxy for the Finnhub trades WebSocket, so clients never see the API token.
+ *
+ * All clients share a single upstream connection. It is opened with the first subscribed
+ * symbol, closed after the last one was unsubscribed and reopened with all symbols
+ * subscribed again whenever it drops.
+ */
+#[derive(Debug, Clone)]
+pub struct TradesProxy {
+    commands: mpsc::UnboundedSender<Command>,
+    next_client: Arc<AtomicU64>,
+}
+
+impl TradesProxy {
+    /**
+     * Starts the proxy for the given WebSocket URL, including the API token if required.
+     */
+    pub fn spawn(url: String) -> TradesProxy {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(Hub::new(url).run(receiver));
+
+        TradesProxy {
+            commands,
+            next_client: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    pub fn connect(&self) -> TradesClient {
+        let client = self.next_client.fetch_add(1, Ordering::Relaxed);
+        let (sender, trades) = mpsc::channel(CLIENT_CHANNEL_CAPACITY);
+
+        // Only fails if the hub stopped, which is reported on the first subscription
+        let _ = self.commands.send(Command::Register {
+            client,
+            trades: sender,
+        });
+
+        TradesClient {
+            id: client,
+            commands: self.commands.clone(),
+            trades,
+        }
+    }
+}
+
+/**
+ * A single client of the proxy, unregisters itself when dropped.
+ */
+#[derive(Debug)]
+pub struct TradesClient {
+    id: u64,
+    commands: mpsc::UnboundedSender<Command>,
+    /**
+     * Trades of the subscribed symbols, in batches as received from Finnhub
+     */
+    pub trades: mpsc::Receiver<Vec<Trade>>,
+}
+
+impl TradesClient {
+    pub async fn subscribe(&self, symbol: &str) -> Result<(), TradesError> {
+        let (reply, response) = oneshot::channel();
+        self.commands
+            .send(Command::Subscribe {
+                client: self.id,
+                symbol: symbol.to_string(),
+                reply,
+            })
+            .map_err(|_| TradesError::ProxyStopped)?;
+
+        response.await.map_err(|_| TradesError::ProxyStopped)?
+    }
+
+    pub fn unsubscribe(&self, symbol: &str) {
+        let _ = self.commands.send(Command::Unsubscribe {
+            client: self.id,
+            symbol: symbol.to_string(),
+        });
+    }
+}
+
+impl Drop for TradesClient {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Unregister { client: self.id });
+    }
+}
+
+/**
+ * Owns the upstream connection and the subscriptions of all clients.
+ */
+struct Hub {
+    /**
+     * Contains the API token, see `error_message`
+     */
+    url: String,
+    clients: HashMap<u64, mpsc::Sender<Vec<Trade>>>,
+    /**
+     * Subscribed clients per symbol, a symbol is only subscribed upstream while it has any
+     */
+    subscriptions: HashMap<String, HashSet<u64>>,
+    upstream: Option<Upstream>,
+    reconnect_attempts: u32,
+    reconnect_at: Instant,
+}
+
+impl Hub {
+    fn new(url: String) -> Hub {
+        Hub {
+            url,
+            clients: HashMap::new(),
+            subscriptions: HashMap::new(),
+            upstream: None,
+            reconnect_attempts: 0,
+            reconnect_at: Instant::now(),
+        }
+    }
+
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<Command>) {
+        loop {
+            let reconnect = self.upstream.is_none() && !self.subscriptions.is_empty();
+
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(command) => self.handle_command(command).await,
+                    // Every proxy handle was dropped
+                    None => return,
+                },
+                message = next_message(&mut self.upstream) => self.handle_upstream(message),
+                _ = tokio::time::sleep_until(self.reconnect_at), if reconnect => self.connect().await,
+            }
+        }
+    }
+
+    async fn connect(&mut self) {
+        match connect_async(self.url.as_str()).await {
+            Ok((upstream, _)) => {
+                tracing::info!("Connected to the trades WebSocket");
+                self.upstream = Some(upstream);
+
+                let symbols: Vec<String> = self.subscriptions.keys().cloned().collect();
+                for symbol in symbols {
+                    self.send_upstream("subscribe", &symbol).await;
+                }
+            }
+            Err(err) => {
+                tracing::warn!(
+                    error = %error_message(&err),
+                    "Failed connecting to the trades WebSocket"
+                );
+                self.schedule_reconnect();
+            }
+        }
+    }
+
+    fn schedule_reconnect(&mut self) {
+        self.upstream = None;
+        let delay = RECONNECT_BASE_DELAY
+            .saturating_mul(2u32.saturating_pow(self.reconnect_attempts))
+            .min(RECONNECT_MAX_DELAY);
+        self.reconnect_attempts += 1;
+        self.reconnect_at = Instant::now() + delay;
+    }
+
+    /**
+     * Sends a (un)subscribe message upstream. A failed send drops the connection, all symbols
+     * are subscribed again after reconnecting.
+     */
+    async fn send_upstream(&mut self, kind: &str, symbol: &str) {
+        let Some(upstream) = self.upstream.as_mut() else {
+            return;
+        };
+
+        let message = json!({ "type": kind, "symbol": symbol }).to_string();
+        if upstream.send(Message::Text(message)).await.is_err() {
+            self.schedule_reconnect();
+        }
+    }
+
+    async fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Register { client, trades } => {
+                self.clients.insert(client, trades);
+            }
+            Command::Subscribe {
+                client,
+                symbol,
+                reply,
+            } => {
+                let result = self.subscribe(client, symbol).await;
+                let _ = reply.send(result);
+            }
+            Command::Unsubscribe { client, symbol } => self.unsubscribe(client, &symbol).await,
+            Command::Unregister { client } => {
+                self.clients.remove(&client);
+                let symbols: Vec<String> = self.subscriptions.keys().cloned().collect();
+                for symbol in symbols {
+                    self.unsubscribe(client, &symbol).await;
+                }
+            }
+        }
+    }
+
+    async fn subscribe(&mut self, client: u64, symbol: String) -> Result<(), TradesError> {
+        if let Some(clients) = self.subscriptions.get_mut(&symbol) {
+            clients.insert(client);
+            return Ok(());
+        }
+
+        if self.subscriptions.len() >= MAX_UPSTREAM_SYMBOLS {
+            return Err(TradesError::TooManySymbols);
+        }
+
+        self.subscriptions
+            .insert(symbol.clone(), HashSet::from([client]));
+        self.send_upstream("subscribe", &symbol).await;
+
+        Ok(())
+    }
+
+    async fn unsubscribe(&mut self, client: u64, symbol: &str) {
+        let Some(clients) = self.subscriptions.get_mut(symbol) else {
+            return;
+        };
+
+        clients.remove(&client);
+        if !clients.is_empty() {
+            return;
+        }
+
+        self.subscriptions.remove(symbol);
+        self.send_upstream("unsubscribe", symbol).await;
+
+        // Nothing left to stream, the connection is opened again with the next subscription
+        if self.subscriptions.is_empty() {
+            if let Some(mut upstream) = self.upstream.take() {
+                let _ = upstream.close(None).await;
+            }
+            self.reconnect_attempts = 0;
+            self.reconnect_at = Instant::now();
+        }
+    }
+
+    fn handle_upstream(&mut self, message: Option<Result<Message, tungstenite::Error>>) {
+        let text = match message {
+            Some(Ok(Message::Text(text))) => {
+                // Only a connection which delivers messages counts as recovered
+                self.reconnect_attempts = 0;
+                text
+            }
+            Some(Err(err)) => {
+                tracing::warn!(
+                    error = %error_message(&err),
+                    "The trades WebSocket connection failed"
+                );
+                self.schedule_reconnect();
+                return;
+            }
+            Some(Ok(Message::Close(_))) | None => {
+                self.schedule_reconnect();
+                return;
+            }
+            // Pings are answered by tungstenite
+            Some(Ok(_)) => return,
+        };
+
+        match serde_json::from_str::<UpstreamMessage>(&text) {
+            Ok(UpstreamMessage::Trade { data }) => self.fan_out(data),
+            Ok(UpstreamMessage::Error { msg }) => {
+                tracing::warn!(error = %msg, "The trades WebSocket reported an error")
+            }
+            Ok(UpstreamMessage::Other) => {}
+            Err(err) => tracing::warn!(error = %err, "Unexpected message on the trades WebSocket"),
+        }
+    }
+
+    /**
+     * Sends every client the trades of the symbols it subscribed to.
+     */
+    fn fan_out(&mut self, trades: Vec<Trade>) {
+        let mut batches: HashMap<u64, Vec<Trade>> = HashMap::new();
+
+        for trade in trades {
+            let Some(clients) = self.subscriptions.get(&trade.symbol) else {
+                continue;
+            };
+            for client in clients {
+                batches.entry(*client).or_default().push(trade.clone());
+            }
+        }
+
+        for (client, batch) in batches {
+            if let Some(sender) = self.clients.get(&client) {
+                // Slow clients miss trades instead of holding up everybody else
+                let _ = sender.try_send(batch);
+            }
+        }
+    }
+}
+
+/**
+ * Message of a WebSocket error for the logs. The upstream URL carries the API token, so it is
+ * redacted in case the error mentions the URL.
+ */
+fn error_message(err: &tungstenite::Error) -> String {
+    logging::redact(&err.to_string())
+}
+
+/**
+ * Next message of the upstream connection, never resolves without a connection.
+ */
+async fn next_message(
+    upstream: &mut Option<Upstream>,
+) -> Option<Result<Message, tungstenite::Error>> {
+    match upstream {
+        Some(upstream) => upstream.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{net::TcpListener, time::timeout};
+    use tokio_tungstenite::accept_async;
+
+    async fn next_text(socket: &mut WebSocketStream<TcpStream>) -> String {
+        loop {
+            match socket.next().await {
+                Some(Ok(Message::Text(text))) => return text,
+                Some(Ok(_)) => continue,
+                other => panic!("expected a text message, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn redacts_the_token_in_error_messages() {
+        let err = tungstenite::Error::Io(std::io::Error::new(
+            std::io::ErrorKind::ConnectionRefused,
+            "wss://ws.finnhub.io/?token=secret refused the connection",
+        ));
+
+        let message = error_message(&err);
+
+        assert!(!message.contains("secret"), "{message}");
+        assert!(message.contains("wss://ws.finnhub.io/?token=REDACTED refused"));
+    }
+
+    #[tokio::test]
+    async fn fans_out_trades_and_resubscribes_after_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let proxy = TradesProxy::spawn(url);
+        let mut client = proxy.connect();
+        client.subscribe("AAPL").await.unwrap();
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut upstream = accept_async(stream).await.unwrap();
+        assert_eq!(
+            next_text(&mut upstream).await,
+            r#"{"symbol":"AAPL","type":"subscribe"}"#
+        );
+
+        let trades = r#"{"type":"trade","data":[
+            {"s":"AAPL","p":189.5,"t":1700000000000,"v":10,"c":null},
+            {"s":"MSFT","p":370.1,"t":1700000000001,"v":5}
+        ]}"#;
+        upstream
+            .send(Message::Text(trades.to_string()))
+            .await
+            .unwrap();
+
+        let batch = timeout(Duration::from_secs(5), client.trades.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].symbol, "AAPL");
+        assert_eq!(batch[0].price, 189.5);
+
+        // Dropping the connection makes the proxy reconnect and subscribe again
+        drop(upstream);
+        let (stream, _) = timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        let mut upstream = accept_async(stream).await.unwrap();
+        assert_eq!(
+            next_text(&mut upstream).await,
+            r#"{"symbol":"AAPL","type":"subscribe"}"#
+        );
+    }
+}
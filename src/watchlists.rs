@@ -0,0 +1,260 @@
+use std::{collections::BTreeMap, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::indices::normalize_symbol;
+
+const MAX_NAME_LENGTH: usize = 100;
+// Every symbol costs one Finnhub call when fetching the quotes of a watchlist
+const MAX_SYMBOLS: usize = 50;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WatchlistError {
+    #[error("No watchlist with id {0}")]
+    NotFound(u64),
+    #[error("The watchlist name must be between 1 and {MAX_NAME_LENGTH} characters long")]
+    InvalidName,
+    #[error("'{0}' is not a valid stock symbol")]
+    InvalidSymbol(String),
+    #[error("A watchlist can hold at most {MAX_SYMBOLS} symbols")]
+    TooManySymbols,
+    #[error("Failed persisting the watchlists: {0}")]
+    Persistence(#[from] io::Error),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Watchlist {
+    pub id: u64,
+    pub name: String,
+    pub symbols: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct WatchlistsFile {
+    next_id: u64,
+    watchlists: BTreeMap<u64, Watchlist>,
+}
+
+/**
+ * Named lists of symbols, persisted as JSON file after every change.
+ */
+#[derive(Debug)]
+pub struct WatchlistStore {
+    path: PathBuf,
+    state: Mutex<WatchlistsFile>,
+}
+
+impl WatchlistStore {
+    /**
+     * Loads the watchlists from the given file. A missing file is treated as no watchlists.
+     */
+    pub fn load(path: PathBuf) -> Result<WatchlistStore, WatchlistError> {
+        let state = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => WatchlistsFile::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(WatchlistStore {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    async fn persist(&self, state: &WatchlistsFile) -> Result<(), WatchlistError> {
+        let json = serde_json::to_vec_pretty(state)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        // Write to a temporary file first, so a crash never leaves a half written file behind
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, json).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+
+        Ok(())
+    }
+
+    pub async fn list(&self) -> Vec<Watchlist> {
+        let state = self.state.lock().await;
+        state.watchlists.values().cloned().collect()
+    }
+
+    pub async fn get(&self, id: u64) -> Result<Watchlist, WatchlistError> {
+        let state = self.state.lock().await;
+        state
+            .watchlists
+            .get(&id)
+            .cloned()
+            .ok_or(WatchlistError::NotFound(id))
+    }
+
+    pub async fn create(
+        &self,
+        name: &str,
+        symbols: &[String],
+    ) -> Result<Watchlist, WatchlistError> {
+        let name = validate_name(name)?;
+        let mut watchlist_symbols = Vec::new();
+        add_symbols(&mut watchlist_symbols, symbols)?;
+
+        self.modify(|state| {
+            state.next_id += 1;
+            let watchlist = Watchlist {
+                id: state.next_id,
+                name,
+                symbols: watchlist_symbols,
+            };
+            state.watchlists.insert(watchlist.id, watchlist.clone());
+            Ok(watchlist)
+        })
+        .await
+    }
+
+    pub async fn rename(&self, id: u64, name: &str) -> Result<Watchlist, WatchlistError> {
+        let name = validate_name(name)?;
+        self.update(id, |watchlist| {
+            watchlist.name = name;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn add_symbols(
+        &self,
+        id: u64,
+        symbols: &[String],
+    ) -> Result<Watchlist, WatchlistError> {
+        self.update(id, |watchlist| add_symbols(&mut watchlist.symbols, symbols))
+            .await
+    }
+
+    pub async fn remove_symbol(&self, id: u64, symbol: &str) -> Result<Watchlist, WatchlistError> {
+        let symbol = normalize_symbol(symbol)
+            .ok_or_else(|| WatchlistError::InvalidSymbol(symbol.to_string()))?;
+        self.update(id, |watchlist| {
+            watchlist.symbols.retain(|existing| existing != &symbol);
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn delete(&self, id: u64) -> Result<(), WatchlistError> {
+        self.modify(|state| {
+            state
+                .watchlists
+                .remove(&id)
+                .ok_or(WatchlistError::NotFound(id))?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update<F>(&self, id: u64, change: F) -> Result<Watchlist, WatchlistError>
+    where
+        F: FnOnce(&mut Watchlist) -> Result<(), WatchlistError>,
+    {
+        self.modify(|state| {
+            let watchlist = state
+                .watchlists
+                .get_mut(&id)
+                .ok_or(WatchlistError::NotFound(id))?;
+            change(watchlist)?;
+            Ok(watchlist.clone())
+        })
+        .await
+    }
+
+    /**
+     * Applies `change` to a copy of the watchlists, which replaces them once it is persisted. A
+     * failing change or write leaves the watchlists untouched.
+     */
+    async fn modify<T, F>(&self, change: F) -> Result<T, WatchlistError>
+    where
+        F: FnOnce(&mut WatchlistsFile) -> Result<T, WatchlistError>,
+    {
+        let mut state = self.state.lock().await;
+        let mut changed = state.clone();
+        let result = change(&mut changed)?;
+
+        self.persist(&changed).await?;
+        *state = changed;
+        Ok(result)
+    }
+}
+
+fn validate_name(name: &str) -> Result<String, WatchlistError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(WatchlistError::InvalidName);
+    }
+    Ok(name.to_string())
+}
+
+/**
+ * Adds the normalized symbols to the list, skipping symbols which are already in it.
+ */
+fn add_symbols(list: &mut Vec<String>, symbols: &[String]) -> Result<(), WatchlistError> {
+    for symbol in symbols {
+        let symbol = normalize_symbol(symbol)
+            .ok_or_else(|| WatchlistError::InvalidSymbol(symbol.to_string()))?;
+        if !list.contains(&symbol) {
+            list.push(symbol);
+        }
+    }
+
+    if list.len() > MAX_SYMBOLS {
+        return Err(WatchlistError::TooManySymbols);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * A directory in the temp directory which is unique to the test, it doesn't exist yet.
+     */
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "giga-stonks-watchlists-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn failed_create_keeps_the_watchlists() {
+        // The directory is missing, so writing the file fails
+        let store = WatchlistStore::load(temp_dir("create").join("watchlists.json")).unwrap();
+
+        let result = store.create("Tech", &["AAPL".to_string()]).await;
+
+        assert!(matches!(result, Err(WatchlistError::Persistence(_))));
+        assert!(store.list().await.is_empty());
+        assert_eq!(store.state.lock().await.next_id, 0);
+    }
+
+    #[tokio::test]
+    async fn failed_update_keeps_the_watchlist() {
+        let dir = temp_dir("update");
+        std::fs::create_dir(&dir).unwrap();
+        let store = WatchlistStore::load(dir.join("watchlists.json")).unwrap();
+        let watchlist = store.create("Tech", &["AAPL".to_string()]).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let renamed = store.rename(watchlist.id, "Big Tech").await;
+        let added = store.add_symbols(watchlist.id, &["MSFT".to_string()]).await;
+        let deleted = store.delete(watchlist.id).await;
+
+        assert!(matches!(renamed, Err(WatchlistError::Persistence(_))));
+        assert!(matches!(added, Err(WatchlistError::Persistence(_))));
+        assert!(matches!(deleted, Err(WatchlistError::Persistence(_))));
+        let unchanged = store.get(watchlist.id).await.unwrap();
+        assert_eq!(unchanged.name, "Tech");
+        assert_eq!(unchanged.symbols, ["AAPL"]);
+    }
+}
@@ -0,0 +1,724 @@
+//! Every route of the API against a fake Finnhub and Alpha Vantage, see `support`.
+
+mod support;
+
+use futures_util::{SinkExt, Stream, StreamExt};
+use giga_stonks_api::{
+    alphavantage_api::lib::AlphaVantageAPI, error::Provider, finnhub_api::lib::FinnhubAPI,
+    retry::RetryPolicy,
+};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::time::Duration;
+use support::{single_attempt, Options, TestApp};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+fn symbols(quotes: &Value) -> Vec<&str> {
+    quotes
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|quote| quote["symbol"].as_str().unwrap())
+        .collect()
+}
+
+async fn next_json<S>(socket: &mut S) -> Value
+where
+    S: Stream<Item = Result<Message, WsError>> + Unpin,
+{
+    let message = timeout(Duration::from_secs(5), socket.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    serde_json::from_str(message.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn serves_the_landing_page_and_unknown_routes() {
+    let app = TestApp::spawn().await;
+
+    let response = app.get("/").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().contains("Giga Stonks API"));
+
+    let response = app.get("/api/v2/quotes").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "No route for /api/v2/quotes"
+    );
+}
+
+#[tokio::test]
+async fn market_news() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app.get_json("/api/v1/market-news").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("finnhub"));
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(
+        body[0]["headline"],
+        "Stocks rally as debt ceiling talks make progress"
+    );
+}
+
+#[tokio::test]
+async fn company_news() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app
+        .get_json("/api/v1/company-news?symbol=AAPL&time_from=2023-05-01&time_to=2023-05-26")
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("finnhub"));
+    assert_eq!(
+        body[0]["headline"],
+        "Apple readies its mixed reality headset"
+    );
+    assert_eq!(
+        app.upstream
+            .requests("company-news?symbol=AAPL&from=2023-05-01&to=2023-05-26"),
+        1
+    );
+}
+
+#[tokio::test]
+async fn company_news_falls_back_to_alpha_vantage_if_finnhub_is_down() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app
+        .get_json("/api/v1/company-news?symbol=DOWN&time_from=2023-05-01&time_to=2023-05-26")
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("alphavantage"));
+    assert_eq!(
+        body[0]["headline"],
+        "Apple Stock Hits Record High Ahead Of WWDC"
+    );
+    // 2023-05-26 14:30 UTC
+    assert_eq!(body[0]["datetime"], 1685111400);
+}
+
+#[tokio::test]
+async fn market_status() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app.get_json("/api/v1/market-status").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("alphavantage"));
+    assert_eq!(body[0]["region"], "United States");
+    assert_eq!(body[1]["current_status"], "closed");
+}
+
+#[tokio::test]
+async fn news_sentiment() {
+    let app = TestApp::spawn().await;
+
+    let (status, _, body) = app
+        .get_json("/api/v1/news-sentiment?time_from=20230526")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["news_bullish"].as_array().unwrap().len(), 1);
+    assert_eq!(body["news_bearish"].as_array().unwrap().len(), 1);
+
+    let (status, provider, body) = app
+        .get_json("/api/v1/news-sentiment-ticker?ticker=AAPL&time_from=20230526")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("alphavantage"));
+    assert_eq!(
+        body["news_bullish"][0]["title"],
+        "Apple Stock Hits Record High Ahead Of WWDC"
+    );
+    assert_eq!(app.upstream.requests("tickers=AAPL"), 1);
+}
+
+#[tokio::test]
+async fn social_sentiment() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app
+        .get_json("/api/v1/social-sentiment?symbol=AAPL&time_from=2023-05-26")
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("finnhub"));
+    assert_eq!(body["reddit"][0]["mention"], 42);
+    assert_eq!(body["twitter"][0]["positiveMention"], 70);
+}
+
+#[tokio::test]
+async fn indices() {
+    let app = TestApp::spawn().await;
+
+    let (status, _, body) = app.get_json("/api/v1/indices").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let djia = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|index| index["id"] == "djia")
+        .unwrap();
+    assert_eq!(djia["constituents"], 30);
+}
+
+#[tokio::test]
+async fn quotes_for_an_index() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app.get_json("/api/v1/quotes/djia").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("finnhub"));
+    assert_eq!(symbols(&body["losers"]), ["MSFT"]);
+    assert_eq!(body["gainers"].as_array().unwrap().len(), 29);
+    assert_eq!(body["failed"], json!([]));
+    assert_eq!(body["rate_limit_remaining"], 59);
+    assert_eq!(app.upstream.requests("/quote?symbol="), 30);
+
+    let (status, _, body) = app.get_json("/api/v1/quotes/ftse").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_input");
+}
+
+#[tokio::test]
+async fn quotes_for_symbols_list_the_failed_symbols() {
+    let app = TestApp::spawn().await;
+
+    let (status, _, body) = app.get_json("/api/v1/quotes?symbols=AAPL,MSFT,BAD").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(symbols(&body["quotes"]), ["AAPL", "MSFT"]);
+    assert_eq!(body["failed"][0]["symbol"], "BAD");
+    assert_eq!(body["failed"][0]["code"], "invalid_upstream_response");
+}
+
+#[tokio::test]
+async fn quote_with_profile() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app.get_json("/api/v1/quote/aapl?include=profile").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("finnhub"));
+    assert_eq!(body["symbol"], "AAPL");
+    assert_eq!(body["current_price"], 177.3);
+    assert_eq!(body["profile"]["name"], "Apple Inc");
+
+    let (status, provider, body) = app.get_json("/api/v1/company-profile/AAPL").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("finnhub"));
+    assert_eq!(body["finnhubIndustry"], "Technology");
+}
+
+#[tokio::test]
+async fn quote_falls_back_to_alpha_vantage_if_finnhub_is_rate_limited() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app.get_json("/api/v1/quote/RATE").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("alphavantage"));
+    assert_eq!(body["symbol"], "RATE");
+    assert_eq!(body["current_price"], 128.89);
+    assert_eq!(body["provider"], "alphavantage");
+}
+
+#[tokio::test]
+async fn finnhub_rate_limit_without_fallback() {
+    let app = TestApp::spawn_with(Options {
+        priority: Some(vec![Provider::Finnhub]),
+        ..Options::default()
+    })
+    .await;
+
+    let (status, _, body) = app.get_json("/api/v1/quote/RATE").await;
+
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "rate_limited");
+    assert_eq!(body["provider"], "finnhub");
+    assert_eq!(app.upstream.requests("function=GLOBAL_QUOTE"), 0);
+}
+
+#[tokio::test]
+async fn alpha_vantage_rate_limit_note() {
+    let app = TestApp::spawn_with(Options {
+        priority: Some(vec![Provider::AlphaVantage]),
+        ..Options::default()
+    })
+    .await;
+
+    let (status, _, body) = app.get_json("/api/v1/quote/AVRATE").await;
+
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "rate_limited");
+    assert_eq!(body["provider"], "alphavantage");
+}
+
+#[tokio::test]
+async fn alpha_vantage_per_minute_limit_keeps_the_daily_quota() {
+    let app = TestApp::spawn_with(Options {
+        priority: Some(vec![Provider::AlphaVantage]),
+        alphavantage_calls_per_day: Some(25),
+        ..Options::default()
+    })
+    .await;
+
+    let (status, _, _) = app.get_json("/api/v1/quote/AVRATE").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, _, _) = app.get_json("/api/v1/quote/IBM").await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, _, body) = app.get_json("/api/v1/status").await;
+    assert_eq!(body["alphavantage"]["daily_quota"]["used"], 2);
+}
+
+#[tokio::test]
+async fn alpha_vantage_per_minute_limit_is_not_retried() {
+    let app = TestApp::spawn_with(Options {
+        priority: Some(vec![Provider::AlphaVantage]),
+        alphavantage_calls_per_day: Some(25),
+        retry_policy: Some(RetryPolicy::default()),
+    })
+    .await;
+
+    let (status, _, _) = app.get_json("/api/v1/quote/AVRATE").await;
+
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(app.upstream.requests("symbol=AVRATE"), 1);
+    let (_, _, body) = app.get_json("/api/v1/status").await;
+    assert_eq!(body["alphavantage"]["daily_quota"]["used"], 1);
+}
+
+#[tokio::test]
+async fn alpha_vantage_daily_limit_exhausts_the_daily_quota() {
+    let app = TestApp::spawn_with(Options {
+        priority: Some(vec![Provider::AlphaVantage]),
+        alphavantage_calls_per_day: Some(25),
+        ..Options::default()
+    })
+    .await;
+
+    let (status, _, _) = app.get_json("/api/v1/quote/AVDAILY").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, _, body) = app.get_json("/api/v1/quote/IBM").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(body["message"].as_str().unwrap().contains("daily quota"));
+    assert_eq!(app.upstream.requests("symbol=IBM"), 0);
+    let (_, _, body) = app.get_json("/api/v1/status").await;
+    assert_eq!(body["alphavantage"]["daily_quota"]["used"], 25);
+}
+
+#[tokio::test]
+async fn alpha_vantage_daily_quota() {
+    let app = TestApp::spawn_with(Options {
+        alphavantage_calls_per_day: Some(1),
+        ..Options::default()
+    })
+    .await;
+
+    let (status, _, _) = app.get_json("/api/v1/market-status").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = app
+        .get_json("/api/v1/news-sentiment?time_from=20230526")
+        .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["provider"], "alphavantage");
+    assert_eq!(app.upstream.requests("function=NEWS_SENTIMENT"), 0);
+}
+
+#[tokio::test]
+async fn malformed_json_is_not_retried_with_another_provider() {
+    let app = TestApp::spawn().await;
+
+    let (status, _, body) = app.get_json("/api/v1/quote/BAD").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["code"], "invalid_upstream_response");
+    assert_eq!(body["provider"], "finnhub");
+
+    // Finnhub's answer for unknown symbols
+    let (status, _, body) = app.get_json("/api/v1/quote/NONE").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "symbol_not_found");
+    assert_eq!(body["provider"], "finnhub");
+
+    assert_eq!(app.upstream.requests("function=GLOBAL_QUOTE"), 0);
+}
+
+#[tokio::test]
+async fn slow_upstream_times_out() {
+    let app = TestApp::spawn_with(Options {
+        priority: Some(vec![Provider::Finnhub]),
+        ..Options::default()
+    })
+    .await;
+
+    let (status, _, body) = app.get_json("/api/v1/quote/SLOW").await;
+
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body["code"], "upstream_timeout");
+    assert_eq!(body["provider"], "finnhub");
+}
+
+#[tokio::test]
+async fn candles_fall_back_to_alpha_vantage() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app.get_json("/api/v1/candles/IBM?resolution=D").await;
+
+    assert_eq!(status, StatusCode::OK);
+    // Finnhub answers 403, candles aren't part of its free plan
+    assert_eq!(provider.as_deref(), Some("alphavantage"));
+    assert_eq!(app.upstream.requests("stock/candle?symbol=IBM"), 1);
+
+    let candles = body["candles"].as_array().unwrap();
+    assert!(!candles.is_empty());
+    assert!(candles
+        .windows(2)
+        .all(|pair| pair[0]["timestamp"].as_i64() < pair[1]["timestamp"].as_i64()));
+    assert_eq!(candles.last().unwrap()["close"], 465.0);
+}
+
+#[tokio::test]
+async fn indicators() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app
+        .get_json("/api/v1/indicators/IBM?set=sma20,rsi14&days=10")
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("alphavantage"));
+    assert_eq!(body["indicators"]["sma20"].as_array().unwrap().len(), 10);
+    assert_eq!(body["indicators"]["rsi14"].as_array().unwrap().len(), 10);
+}
+
+#[tokio::test]
+async fn earnings_calendar() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, body) = app.get_json("/api/v1/earnings-calendar").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("finnhub"));
+    assert_eq!(symbols(&body["estimates_high"]), ["MSFT"]);
+    assert_eq!(symbols(&body["estimates_low"]), ["RIVN"]);
+
+    let app = TestApp::spawn_with(Options {
+        priority: Some(vec![Provider::AlphaVantage]),
+        ..Options::default()
+    })
+    .await;
+
+    let (status, provider, body) = app.get_json("/api/v1/earnings-calendar").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("alphavantage"));
+    assert_eq!(body["estimates_high"][0]["name"], "Microsoft Corporation");
+    assert_eq!(symbols(&body["estimates_low"]), ["RIVN"]);
+}
+
+#[tokio::test]
+async fn health_and_readiness() {
+    let app = TestApp::spawn().await;
+
+    let (status, _, body) = app.get_json("/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+
+    let (status, _, body) = app.get_json("/readyz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["providers"]["finnhub"]["reachable"], true);
+    assert_eq!(body["providers"]["alphavantage"]["reachable"], true);
+    // Without credentials, so they don't count against the limits
+    assert_eq!(app.upstream.requests("X-Finnhub-Token"), 0);
+    assert_eq!(app.upstream.requests("apikey="), 0);
+}
+
+#[tokio::test]
+async fn finnhub_token_is_sent_as_a_header() {
+    let app = TestApp::spawn().await;
+
+    let (status, provider, _) = app.get_json("/api/v1/quote/AAPL").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("finnhub"));
+    assert_eq!(
+        app.upstream
+            .requests("/quote?symbol=AAPL X-Finnhub-Token: finnhub-token"),
+        1
+    );
+    assert_eq!(app.upstream.requests("token="), 0);
+}
+
+#[tokio::test]
+async fn upstream_errors_do_not_contain_api_keys() {
+    // Nothing listens on the port once the listener is dropped
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let http_client = reqwest::Client::new();
+
+    let mut finnhub = FinnhubAPI::new("finnhub-secret", http_client.clone());
+    finnhub
+        .base_url(format!("http://{addr}/api/v1").parse().unwrap())
+        .retry_policy(single_attempt());
+    let err = finnhub.fetch_market_news().await.unwrap_err();
+    assert!(err.to_string().contains("market news request failed"));
+    assert!(!err.to_string().contains("finnhub-secret"));
+
+    let mut alphavantage = AlphaVantageAPI::new("alphavantage-secret", http_client);
+    alphavantage
+        .base_url(format!("http://{addr}/query").parse().unwrap())
+        .retry_policy(single_attempt());
+    let err = alphavantage.fetch_market_status().await.unwrap_err();
+    assert!(err.to_string().contains("market status request failed"));
+    assert!(!err.to_string().contains("alphavantage-secret"));
+}
+
+#[tokio::test]
+async fn request_ids() {
+    let app = TestApp::spawn().await;
+
+    // Generated if the client didn't send one
+    let response = app.get("/api/v1/quote/AAPL").await;
+    let generated = response.headers()["x-request-id"].to_str().unwrap();
+    assert_eq!(generated.len(), 36);
+
+    let response = app
+        .client
+        .get(format!("{}/api/v1/quote/AAPL", app.url))
+        .header("x-request-id", "client-request-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"], "client-request-1");
+
+    // Also on errors outside of the API routes
+    let response = app.get("/unknown").await;
+    assert!(response.headers().contains_key("x-request-id"));
+}
+
+#[tokio::test]
+async fn provider_status() {
+    let app = TestApp::spawn_with(Options {
+        alphavantage_calls_per_day: Some(25),
+        ..Options::default()
+    })
+    .await;
+
+    let (_, _, body) = app.get_json("/api/v1/status").await;
+    assert_eq!(body["finnhub"]["last_success"], Value::Null);
+    assert_eq!(body["finnhub"]["rate_limit"], Value::Null);
+    assert_eq!(body["alphavantage"]["daily_quota"]["used"], 0);
+
+    app.get_json("/api/v1/quote/AAPL").await;
+    // Falls back to Alpha Vantage
+    app.get_json("/api/v1/quote/RATE").await;
+
+    let (status, _, body) = app.get_json("/api/v1/status").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["finnhub"]["last_success"].is_i64());
+    assert!(body["finnhub"]["last_error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("rate limit"));
+    assert_eq!(body["finnhub"]["rate_limit"]["ratelimit_remaining"], "59");
+    assert!(body["alphavantage"]["last_success"].is_i64());
+    assert_eq!(body["alphavantage"]["last_error"], Value::Null);
+    assert_eq!(body["alphavantage"]["daily_quota"]["used"], 1);
+    assert_eq!(body["alphavantage"]["daily_quota"]["limit"], 25);
+}
+
+#[tokio::test]
+async fn responses_are_cached() {
+    let app = TestApp::spawn().await;
+
+    let first = app.get("/api/v1/market-status").await;
+    assert_eq!(first.headers()["x-cache"], "MISS");
+
+    let second = app.get("/api/v1/market-status").await;
+    assert_eq!(second.headers()["x-cache"], "HIT");
+    assert_eq!(second.headers()["x-data-provider"], "alphavantage");
+    assert_eq!(app.upstream.requests("function=MARKET_STATUS"), 1);
+}
+
+#[tokio::test]
+async fn watchlist_quotes_are_not_cached() {
+    let app = TestApp::spawn().await;
+
+    let (_, watchlist) = app
+        .send_json(
+            Method::POST,
+            "/api/v1/watchlists",
+            json!({ "name": "Tech", "symbols": ["AAPL"] }),
+        )
+        .await;
+    let quotes_path = format!("/api/v1/quotes/watchlist/{}", watchlist["id"]);
+
+    let response = app.get(&quotes_path).await;
+    assert_eq!(response.headers()["cache-control"], "no-store");
+    assert!(response.headers().get("x-cache").is_none());
+
+    app.send_json(
+        Method::POST,
+        &format!("/api/v1/watchlists/{}/symbols", watchlist["id"]),
+        json!({ "symbols": ["MSFT"] }),
+    )
+    .await;
+
+    let response = app.get(&quotes_path).await;
+    let summary: Value = response.json().await.unwrap();
+    assert_eq!(symbols(&summary["losers"]), ["MSFT"]);
+}
+
+#[tokio::test]
+async fn metrics() {
+    let app = TestApp::spawn().await;
+
+    app.get_json("/api/v1/quote/AAPL").await;
+    app.get_json("/api/v1/quote/AAPL").await;
+    // Falls back to Alpha Vantage
+    app.get_json("/api/v1/quote/RATE").await;
+    app.get_json("/healthz").await;
+
+    let response = app.get("/metrics").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let body = response.text().await.unwrap();
+
+    for line in [
+        r#"http_requests_total{route="/api/v1/quote/:symbol",method="GET",status="200"} 3"#,
+        r#"http_requests_total{route="/healthz",method="GET",status="200"} 1"#,
+        r#"http_request_duration_seconds_count{route="/api/v1/quote/:symbol",method="GET"} 3"#,
+        r#"upstream_requests_total{provider="finnhub"} 2"#,
+        r#"upstream_requests_total{provider="alphavantage"} 1"#,
+        r#"upstream_retries_exhausted_total{provider="finnhub"} 1"#,
+        r#"upstream_errors_total{provider="finnhub",error="rate_limited"} 1"#,
+        "cache_hits_total 1",
+        "cache_misses_total 2",
+        "finnhub_ratelimit_remaining 59",
+    ] {
+        assert!(body.lines().any(|l| l == line), "missing {line} in\n{body}");
+    }
+}
+
+#[tokio::test]
+async fn watchlists() {
+    let app = TestApp::spawn().await;
+
+    let (status, watchlist) = app
+        .send_json(
+            Method::POST,
+            "/api/v1/watchlists",
+            json!({ "name": "Tech", "symbols": ["aapl"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(watchlist["symbols"], json!(["AAPL"]));
+    let path = format!("/api/v1/watchlists/{}", watchlist["id"]);
+
+    let (status, watchlist) = app
+        .send_json(
+            Method::POST,
+            &format!("{path}/symbols"),
+            json!({ "symbols": ["MSFT", "BAD"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(watchlist["symbols"], json!(["AAPL", "MSFT", "BAD"]));
+
+    let (status, provider, summary) = app
+        .get_json(&format!("/api/v1/quotes/watchlist/{}", watchlist["id"]))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(provider.as_deref(), Some("finnhub"));
+    assert_eq!(symbols(&summary["gainers"]), ["AAPL"]);
+    assert_eq!(symbols(&summary["losers"]), ["MSFT"]);
+    assert_eq!(summary["failed"][0]["symbol"], "BAD");
+
+    let (status, watchlist) = app
+        .send_json(Method::DELETE, &format!("{path}/symbols/BAD"), json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(watchlist["symbols"], json!(["AAPL", "MSFT"]));
+
+    let (status, watchlist) = app
+        .send_json(Method::PATCH, &path, json!({ "name": "Big Tech" }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(watchlist["name"], "Big Tech");
+
+    let (status, _, watchlists) = app.get_json("/api/v1/watchlists").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(watchlists.as_array().unwrap().len(), 1);
+
+    let (status, _) = app.send_json(Method::DELETE, &path, json!({})).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _, body) = app.get_json(&path).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+}
+
+#[tokio::test]
+async fn quote_stream_starts_with_a_snapshot() {
+    let app = TestApp::spawn().await;
+
+    let mut response = app.get("/api/v1/stream/quotes/djia").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+    let mut events = String::new();
+    timeout(Duration::from_secs(10), async {
+        while !events.contains("\n\n") {
+            let chunk = response.chunk().await.unwrap().unwrap();
+            events.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    })
+    .await
+    .unwrap();
+
+    assert!(events.starts_with("event:snapshot\n"));
+    let data = events
+        .lines()
+        .find_map(|line| line.strip_prefix("data:"))
+        .unwrap();
+    let snapshot: Value = serde_json::from_str(data).unwrap();
+    assert_eq!(symbols(&snapshot["losers"]), ["MSFT"]);
+}
+
+#[tokio::test]
+async fn trades_socket_relays_upstream_trades() {
+    let app = TestApp::spawn().await;
+
+    let url = format!("{}/api/v1/stream/trades", app.url.replace("http", "ws"));
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    socket
+        .send(Message::Text(
+            json!({ "action": "subscribe", "symbol": "aapl" }).to_string(),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        next_json(&mut socket).await,
+        json!({ "type": "subscribed", "symbol": "AAPL" })
+    );
+    let trades = next_json(&mut socket).await;
+    assert_eq!(trades["type"], "trades");
+    assert_eq!(trades["data"][0]["symbol"], "AAPL");
+    assert_eq!(trades["data"][0]["price"], 177.31);
+}
@@ -0,0 +1,484 @@
+//! A fake Finnhub and Alpha Vantage on a local port, and the API running against it.
+//!
+//! The upstream answers with the recorded responses in `tests/fixtures`. Some symbols trigger
+//! failure scenarios instead, for every endpoint which takes a symbol. Alpha Vantage uses the
+//! same symbols prefixed with `AV`, so a failure of Finnhub can fall back to Alpha Vantage:
+//!
+//! - `RATE`: Finnhub answers 429, Alpha Vantage with its per-minute rate limit note
+//! - `AVDAILY`: Alpha Vantage's message for the exhausted daily limit
+//! - `BAD`: malformed JSON
+//! - `SLOW`: the response takes longer than the client timeout
+//! - `DOWN`: 500 Internal Server Error
+//! - `NONE`: Finnhub's quote of zeros for unknown symbols
+
+#![allow(dead_code)]
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use giga_stonks_api::{
+    alphavantage_api::lib::AlphaVantageAPI,
+    cache::{CacheConfig, ResponseCache},
+    candles::{format_date, unix_now},
+    error::Provider,
+    finnhub_api::lib::FinnhubAPI,
+    http_client::HttpClientConfig,
+    indices::IndexRegistry,
+    provider::MarketData,
+    rate_limit::{DailyQuota, TokenBucket},
+    retry::RetryPolicy,
+    router,
+    trades::TradesProxy,
+    watchlists::WatchlistStore,
+    AppState,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use url::Url;
+
+/**
+ * Timeout of the API's upstream requests, `SLOW` responses take twice as long.
+ */
+pub const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+fn fixture(path: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(path);
+    std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()))
+}
+
+fn json_response(status: StatusCode, body: String) -> Response {
+    (status, [(CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+type RequestLog = Arc<Mutex<Vec<String>>>;
+
+pub struct MockUpstream {
+    addr: SocketAddr,
+    requests: RequestLog,
+}
+
+impl MockUpstream {
+    pub async fn start() -> MockUpstream {
+        let requests = RequestLog::default();
+        let app = Router::new()
+            .route("/finnhub/api/v1/*path", get(finnhub))
+            .route("/finnhub/ws", get(finnhub_trades))
+            .route("/alphavantage/query", get(alphavantage))
+            .with_state(requests.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+
+        MockUpstream { addr, requests }
+    }
+
+    pub fn finnhub_url(&self) -> Url {
+        format!("http://{}/finnhub/api/v1", self.addr)
+            .parse()
+            .unwrap()
+    }
+
+    pub fn finnhub_ws_url(&self) -> String {
+        format!("ws://{}/finnhub/ws", self.addr)
+    }
+
+    pub fn alphavantage_url(&self) -> Url {
+        format!("http://{}/alphavantage/query", self.addr)
+            .parse()
+            .unwrap()
+    }
+
+    /**
+     * Number of upstream requests whose path and query contain `pattern`,
+     * e.g. "function=GLOBAL_QUOTE" or "quote?symbol=AAPL". Finnhub requests with a token
+     * header end with " X-Finnhub-Token: <token>".
+     */
+    pub fn requests(&self, pattern: &str) -> usize {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.contains(pattern))
+            .count()
+    }
+}
+
+fn log_request(requests: &RequestLog, uri: &Uri) {
+    let request = uri.path_and_query().map(ToString::to_string);
+    requests.lock().unwrap().push(request.unwrap_or_default());
+}
+
+async fn finnhub(
+    Path(path): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    State(requests): State<RequestLog>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    let token = headers
+        .get("x-finnhub-token")
+        .and_then(|value| value.to_str().ok());
+    let mut request = uri
+        .path_and_query()
+        .map(ToString::to_string)
+        .unwrap_or_default();
+    if let Some(token) = token {
+        request.push_str(&format!(" X-Finnhub-Token: {token}"));
+    }
+    requests.lock().unwrap().push(request);
+
+    if token != Some("finnhub-token") {
+        return json_response(
+            StatusCode::UNAUTHORIZED,
+            json!({ "error": "Invalid API key" }).to_string(),
+        );
+    }
+
+    let symbol = params.get("symbol").map(String::as_str).unwrap_or_default();
+    match symbol {
+        "RATE" => {
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [("retry-after", "1")],
+                json!({ "error": "API limit reached. Please try again later." }).to_string(),
+            )
+                .into_response()
+        }
+        "BAD" => return json_response(StatusCode::OK, r#"{"c": 177.3, "d":"#.to_string()),
+        "SLOW" => tokio::time::sleep(REQUEST_TIMEOUT * 2).await,
+        "DOWN" => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        _ => {}
+    }
+
+    let body = match path.trim_start_matches('/') {
+        "quote" if symbol == "NONE" => json!({
+            "c": 0, "d": null, "dp": null, "h": 0, "l": 0, "o": 0, "pc": 0, "t": 0
+        })
+        .to_string(),
+        "quote" if symbol == "MSFT" => fixture("finnhub/quote_MSFT.json"),
+        "quote" => fixture("finnhub/quote.json"),
+        "news" => fixture("finnhub/market_news.json"),
+        "company-news" => fixture("finnhub/company_news.json"),
+        "stock/profile2" => fixture("finnhub/company_profile.json"),
+        "stock/social-sentiment" => fixture("finnhub/social_sentiment.json"),
+        "calendar/earnings" => fixture("finnhub/earnings_calendar.json"),
+        // Candles aren't part of the free plan
+        "stock/candle" => {
+            return json_response(
+                StatusCode::FORBIDDEN,
+                json!({ "error": "You don't have access to this resource." }).to_string(),
+            )
+        }
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, "application/json".to_string()),
+            ("x-ratelimit-limit".parse().unwrap(), "60".to_string()),
+            ("x-ratelimit-remaining".parse().unwrap(), "59".to_string()),
+            (
+                "x-ratelimit-reset".parse().unwrap(),
+                (unix_now() + 60).to_string(),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+async fn alphavantage(
+    Query(params): Query<HashMap<String, String>>,
+    State(requests): State<RequestLog>,
+    uri: Uri,
+) -> Response {
+    log_request(&requests, &uri);
+
+    if params.get("apikey").map(String::as_str) != Some("alphavantage-token") {
+        let message = "the parameter apikey is invalid or missing. Please claim your free API \
+            key on (https://www.alphavantage.co/support/#api-key).";
+        return json_response(
+            StatusCode::OK,
+            json!({ "Error Message": message }).to_string(),
+        );
+    }
+
+    let symbol = params
+        .get("symbol")
+        .or_else(|| params.get("tickers"))
+        .map(String::as_str)
+        .unwrap_or_default();
+    match symbol {
+        "AVRATE" => {
+            let note = "Thank you for using Alpha Vantage! Our standard API call frequency is 5 \
+                calls per minute and 100 calls per day.";
+            return json_response(StatusCode::OK, json!({ "Note": note }).to_string());
+        }
+        "AVDAILY" => {
+            let information = "Thank you for using Alpha Vantage! Our standard API rate limit \
+                is 25 requests per day.";
+            return json_response(
+                StatusCode::OK,
+                json!({ "Information": information }).to_string(),
+            );
+        }
+        "AVBAD" => return json_response(StatusCode::OK, r#"{"Global Quote": {"#.to_string()),
+        "AVSLOW" => tokio::time::sleep(REQUEST_TIMEOUT * 2).await,
+        "AVDOWN" => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        _ => {}
+    }
+
+    let function = params.get("function").map(String::as_str);
+    let body = match function.unwrap_or_default() {
+        "MARKET_STATUS" => fixture("alphavantage/market_status.json"),
+        "NEWS_SENTIMENT" => fixture("alphavantage/news_sentiment.json"),
+        "GLOBAL_QUOTE" => fixture("alphavantage/global_quote.json"),
+        "TIME_SERIES_DAILY" => daily_time_series(symbol),
+        "EARNINGS_CALENDAR" => {
+            return (
+                StatusCode::OK,
+                [(CONTENT_TYPE, "application/x-download")],
+                fixture("alphavantage/earnings_calendar.csv"),
+            )
+                .into_response()
+        }
+        _ => {
+            let message = "Invalid API call. Please retry or visit the documentation \
+                (https://www.alphavantage.co/documentation/) for TIME_SERIES_DAILY.";
+            json!({ "Error Message": message }).to_string()
+        }
+    };
+
+    json_response(StatusCode::OK, body)
+}
+
+/**
+ * A daily series up to today, generated because candle and indicator ranges are relative to
+ * the current time. The close rises by 1 every day, starting at 100 one year ago.
+ */
+fn daily_time_series(symbol: &str) -> String {
+    let today = unix_now() - unix_now().rem_euclid(SECONDS_PER_DAY);
+    let series: serde_json::Map<String, Value> = (0..=365)
+        .map(|days_ago| {
+            let close = 465.0 - days_ago as f64;
+            let entry = json!({
+                "1. open": format!("{:.4}", close - 0.5),
+                "2. high": format!("{:.4}", close + 1.0),
+                "3. low": format!("{:.4}", close - 1.0),
+                "4. close": format!("{close:.4}"),
+                "5. volume": "1000000",
+            });
+            (format_date(today - days_ago * SECONDS_PER_DAY), entry)
+        })
+        .collect();
+
+    json!({
+        "Meta Data": {
+            "1. Information": "Daily Prices (open, high, low, close) and Volumes",
+            "2. Symbol": symbol,
+            "3. Last Refreshed": format_date(today),
+            "4. Output Size": "Compact",
+            "5. Time Zone": "US/Eastern",
+        },
+        "Time Series (Daily)": series,
+    })
+    .to_string()
+}
+
+/**
+ * Answers every subscription with a single trade of the symbol.
+ */
+async fn finnhub_trades(ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(|mut socket: WebSocket| async move {
+        while let Some(Ok(message)) = socket.recv().await {
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let request: Value = serde_json::from_str(&text).unwrap_or_default();
+            if request["type"] != "subscribe" {
+                continue;
+            }
+
+            let trade = json!({
+                "type": "trade",
+                "data": [{ "s": request["symbol"], "p": 177.31, "t": 1685131201000u64, "v": 25, "c": ["1"] }],
+            });
+            if socket.send(Message::Text(trade.to_string())).await.is_err() {
+                return;
+            }
+        }
+    })
+}
+
+/**
+ * Settings of the API under test which differ between the tests.
+ */
+#[derive(Default)]
+pub struct Options {
+    pub priority: Option<Vec<Provider>>,
+    pub alphavantage_calls_per_day: Option<u32>,
+    /**
+     * The upstream calls are only attempted once by default
+     */
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+pub struct TestApp {
+    pub url: String,
+    pub upstream: MockUpstream,
+    pub client: reqwest::Client,
+}
+
+impl TestApp {
+    pub async fn spawn() -> TestApp {
+        TestApp::spawn_with(Options::default()).await
+    }
+
+    pub async fn spawn_with(options: Options) -> TestApp {
+        let upstream = MockUpstream::start().await;
+
+        let http_client = HttpClientConfig {
+            request_timeout: REQUEST_TIMEOUT,
+            ..HttpClientConfig::default()
+        }
+        .build()
+        .unwrap();
+        // The scenarios should fail right away
+        let retry_policy = || options.retry_policy.clone().unwrap_or_else(single_attempt);
+
+        let mut finnhub = FinnhubAPI::new("finnhub-token", http_client.clone());
+        finnhub
+            .base_url(upstream.finnhub_url())
+            .retry_policy(retry_policy())
+            .rate_limiter(TokenBucket::new(100, 600, Duration::from_secs(1)));
+
+        let mut alphavantage = AlphaVantageAPI::new("alphavantage-token", http_client);
+        alphavantage
+            .base_url(upstream.alphavantage_url())
+            .retry_policy(retry_policy());
+        if let Some(per_day) = options.alphavantage_calls_per_day {
+            alphavantage.quota(DailyQuota::new(per_day));
+        }
+
+        let mut market_data = MarketData::new(vec![
+            Arc::new(finnhub.clone()),
+            Arc::new(alphavantage.clone()),
+        ]);
+        if let Some(priority) = options.priority {
+            market_data.priority(priority);
+        }
+
+        let app_state = Arc::new(AppState::new(
+            finnhub,
+            alphavantage,
+            market_data,
+            ResponseCache::new(CacheConfig::default()),
+            IndexRegistry::embedded(),
+            WatchlistStore::load(watchlists_path()).unwrap(),
+            TradesProxy::spawn(upstream.finnhub_ws_url()),
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router(app_state).into_make_service());
+        tokio::spawn(server);
+
+        TestApp {
+            url: format!("http://{addr}"),
+            upstream,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn get(&self, path: &str) -> reqwest::Response {
+        self.client
+            .get(format!("{}{path}", self.url))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    /**
+     * Status, `X-Data-Provider` header and JSON body of a GET request.
+     */
+    pub async fn get_json(&self, path: &str) -> (StatusCode, Option<String>, Value) {
+        let response = self.get(path).await;
+        let status = response.status();
+        let provider = response
+            .headers()
+            .get("x-data-provider")
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string);
+        let body = response.json().await.unwrap();
+
+        (status, provider, body)
+    }
+
+    pub async fn send_json(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let response = self
+            .client
+            .request(method, format!("{}{path}", self.url))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+
+        (status, response.json().await.unwrap_or_default())
+    }
+}
+
+/**
+ * Retry policy which gives up after the first failed attempt.
+ */
+pub fn single_attempt() -> RetryPolicy {
+    let mut retry_policy = RetryPolicy::default();
+    retry_policy.max_attempts = 1;
+    retry_policy
+}
+
+/**
+ * A path in the temp directory which is unique to the test, the file doesn't exist yet.
+ */
+fn watchlists_path() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!(
+        "giga-stonks-watchlists-{}-{n}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}